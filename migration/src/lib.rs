@@ -6,6 +6,14 @@ mod m20241020_004011_create_jogo;
 mod m20241031_011703_cria_apuracao;
 mod m20241117_150055_lista_extra;
 mod m20241123_213837_imagens;
+mod m20241201_000000_verificacao_email;
+mod m20241208_000000_votacao_por_jogo;
+mod m20241215_000000_gestao_jogos;
+mod m20241222_000000_inscricao;
+mod m20241229_000000_tamanho_cedula;
+mod m20250105_000000_estado_cedula;
+mod m20250112_000000_revisao_cedula;
+mod m20250119_000000_rating;
 
 pub struct Migrator;
 
@@ -19,6 +27,14 @@ impl MigratorTrait for Migrator {
             Box::new(m20241031_011703_cria_apuracao::Migration),
             Box::new(m20241117_150055_lista_extra::Migration),
             Box::new(m20241123_213837_imagens::Migration),
+            Box::new(m20241201_000000_verificacao_email::Migration),
+            Box::new(m20241208_000000_votacao_por_jogo::Migration),
+            Box::new(m20241215_000000_gestao_jogos::Migration),
+            Box::new(m20241222_000000_inscricao::Migration),
+            Box::new(m20241229_000000_tamanho_cedula::Migration),
+            Box::new(m20250105_000000_estado_cedula::Migration),
+            Box::new(m20250112_000000_revisao_cedula::Migration),
+            Box::new(m20250119_000000_rating::Migration),
         ]
     }
 }
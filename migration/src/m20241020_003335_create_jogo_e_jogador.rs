@@ -50,4 +50,6 @@ pub(crate) enum Jogador {
     Admin,
     // Added by m20241123_213837_imagens.rs
     Imagem,
+    // Added by m20241201_000000_verificacao_email.rs
+    Ativo,
 }
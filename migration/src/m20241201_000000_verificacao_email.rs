@@ -0,0 +1,77 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20241020_003335_create_jogo_e_jogador::Jogador;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Jogadores que já existem foram criados manualmente, então já estão ativos
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogador::Table)
+                    .add_column(
+                        ColumnDef::new(Jogador::Ativo)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Token::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Token::Id))
+                    .col(integer(Token::JogadorId))
+                    .col(string(Token::Tipo))
+                    .col(string_uniq(Token::Hash))
+                    .col(timestamp(Token::ExpiraEm))
+                    .col(timestamp_null(Token::UsadoEm))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_token_jogador_id")
+                            .from(Token::Table, Token::JogadorId)
+                            .to(Jogador::Table, Jogador::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Token::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogador::Table)
+                    .drop_column(Jogador::Ativo)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Token {
+    Table,
+    Id,
+    JogadorId,
+    Tipo,
+    Hash,
+    ExpiraEm,
+    UsadoEm,
+}
@@ -0,0 +1,102 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogo::Table)
+                    .add_column(
+                        ColumnDef::new(Jogo::Hora)
+                            .time()
+                            .not_null()
+                            .default("19:00:00"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Cédulas antigas não têm jogo, elas continuam valendo só para a semana
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Ballot::Table)
+                    .add_column(ColumnDef::new(Ballot::JogoId).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApuracaoJogo::Table)
+                    .if_not_exists()
+                    .col(pk_auto(ApuracaoJogo::Id))
+                    .col(integer_uniq(ApuracaoJogo::JogoId))
+                    .col(string(ApuracaoJogo::State))
+                    .col(json(ApuracaoJogo::Results))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_apuracao_jogo_jogo_id")
+                            .from(ApuracaoJogo::Table, ApuracaoJogo::JogoId)
+                            .to(Jogo::Table, Jogo::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApuracaoJogo::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Ballot::Table)
+                    .drop_column(Ballot::JogoId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogo::Table)
+                    .drop_column(Jogo::Hora)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Jogo {
+    Table,
+    Id,
+    Hora,
+}
+
+#[derive(DeriveIden)]
+enum Ballot {
+    Table,
+    JogoId,
+}
+
+#[derive(DeriveIden)]
+enum ApuracaoJogo {
+    Table,
+    Id,
+    JogoId,
+    State,
+    Results,
+}
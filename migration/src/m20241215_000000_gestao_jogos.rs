@@ -0,0 +1,69 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Antes a capacidade era fixa em 24 jogadores
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogo::Table)
+                    .add_column(
+                        ColumnDef::new(Jogo::Capacidade)
+                            .integer()
+                            .not_null()
+                            .default(24),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogo::Table)
+                    .add_column(
+                        ColumnDef::new(Jogo::Cancelado)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogo::Table)
+                    .drop_column(Jogo::Cancelado)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogo::Table)
+                    .drop_column(Jogo::Capacidade)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Jogo {
+    Table,
+    Capacidade,
+    Cancelado,
+}
@@ -0,0 +1,73 @@
+use crate::m20241020_003335_create_jogo_e_jogador::Jogador;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Inscricao::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Inscricao::Id))
+                    .col(integer(Inscricao::JogoId))
+                    .col(integer(Inscricao::JogadorId))
+                    .col(timestamp(Inscricao::CriadoEm))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_inscricao_jogo_id")
+                            .from(Inscricao::Table, Inscricao::JogoId)
+                            .to(Jogo::Table, Jogo::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_inscricao_jogador_id")
+                            .from(Inscricao::Table, Inscricao::JogadorId)
+                            .to(Jogador::Table, Jogador::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Cada jogador só pode se inscrever uma vez em cada jogo
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_inscricao_jogo_jogador")
+                    .table(Inscricao::Table)
+                    .col(Inscricao::JogoId)
+                    .col(Inscricao::JogadorId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Inscricao::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Jogo {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Inscricao {
+    Table,
+    Id,
+    JogoId,
+    JogadorId,
+    CriadoEm,
+}
@@ -0,0 +1,74 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Antes toda cédula tinha 5 candidatos
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Ballot::Table)
+                    .add_column(
+                        ColumnDef::new(Ballot::Tamanho)
+                            .integer()
+                            .not_null()
+                            .default(5),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogo::Table)
+                    .add_column(
+                        ColumnDef::new(Jogo::TamanhoCedula)
+                            .integer()
+                            .not_null()
+                            .default(5),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Jogo::Table)
+                    .drop_column(Jogo::TamanhoCedula)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Ballot::Table)
+                    .drop_column(Ballot::Tamanho)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Ballot {
+    Table,
+    Tamanho,
+}
+
+#[derive(DeriveIden)]
+enum Jogo {
+    Table,
+    TamanhoCedula,
+}
@@ -0,0 +1,180 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Estados que o código gravava antes de usar o enum `ballot_state`.
+///
+/// `closed` valia tanto para cédulas votadas quanto para as já apuradas; o que
+/// separa umas das outras é existir a apuração da semana ou do jogo.
+const LEGADO: [(&str, BallotState); 2] = [
+    ("open", BallotState::Generated),
+    ("closed", BallotState::Submitted),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Ballot::Table)
+                    .value(Ballot::State, BallotState::Computed.to_string())
+                    .cond_where(
+                        Cond::all()
+                            .add(Expr::col((Ballot::Table, Ballot::State)).eq("closed"))
+                            .add(
+                                Cond::any()
+                                    .add(Expr::exists(
+                                        Query::select()
+                                            .expr(Expr::val(1))
+                                            .from(Apuracao::Table)
+                                            .and_where(
+                                                Expr::col((Apuracao::Table, Apuracao::WeekId))
+                                                    .equals((Ballot::Table, Ballot::FuteId)),
+                                            )
+                                            .to_owned(),
+                                    ))
+                                    .add(Expr::exists(
+                                        Query::select()
+                                            .expr(Expr::val(1))
+                                            .from(ApuracaoJogo::Table)
+                                            .and_where(
+                                                Expr::col((
+                                                    ApuracaoJogo::Table,
+                                                    ApuracaoJogo::JogoId,
+                                                ))
+                                                .equals((Ballot::Table, Ballot::JogoId)),
+                                            )
+                                            .to_owned(),
+                                    )),
+                            ),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        for (antigo, novo) in LEGADO {
+            manager
+                .exec_stmt(
+                    Query::update()
+                        .table(Ballot::Table)
+                        .value(Ballot::State, novo.to_string())
+                        .and_where(Expr::col(Ballot::State).eq(antigo))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Cédulas apuradas também eram "closed"
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Ballot::Table)
+                    .value(Ballot::State, "closed")
+                    .and_where(Expr::col(Ballot::State).is_in([
+                        BallotState::Submitted.to_string(),
+                        BallotState::Computed.to_string(),
+                    ]))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Ballot::Table)
+                    .value(Ballot::State, "open")
+                    .and_where(Expr::col(Ballot::State).eq(BallotState::Generated.to_string()))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Ballot {
+    Table,
+    State,
+    FuteId,
+    JogoId,
+}
+
+#[derive(DeriveIden)]
+enum Apuracao {
+    Table,
+    WeekId,
+}
+
+#[derive(DeriveIden)]
+enum ApuracaoJogo {
+    Table,
+    JogoId,
+}
+
+#[derive(Iden)]
+enum BallotState {
+    #[iden = "generated"]
+    Generated,
+    #[iden = "submitted"]
+    Submitted,
+    #[iden = "computed"]
+    Computed,
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm_migration::sea_orm::{ConnectOptions, ConnectionTrait, Database, Statement};
+
+    use super::Migration;
+    use crate::{MigrationName, Migrator, MigratorTrait};
+
+    /// Quantas migrações vêm antes desta, pela posição do nome na lista.
+    fn anteriores() -> u32 {
+        Migrator::migrations()
+            .iter()
+            .position(|m| m.name() == Migration.name())
+            .unwrap() as u32
+    }
+
+    #[async_std::test]
+    async fn closed_vira_computed_so_com_apuracao() {
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+        let db = Database::connect(options).await.unwrap();
+        Migrator::up(&db, Some(anteriores())).await.unwrap();
+        db.execute_unprepared(
+            "INSERT INTO jogo (id, nome, data, local) VALUES (7, 'Fute', '2024-12-20', 'Quadra');
+            INSERT INTO apuracao (week_id, random_id, state, results) VALUES (1, 'a', 'complete', '{}');
+            INSERT INTO apuracao_jogo (jogo_id, state, results) VALUES (7, 'complete', '{}');
+            INSERT INTO ballot (id, players, vote, date, voter, fute_id, state, jogo_id) VALUES
+                (1, '[]', '[]', '2024-12-01', 'v', 1, 'closed', NULL),
+                (2, '[]', '[]', '2024-12-08', 'v', 2, 'closed', NULL),
+                (3, '[]', '[]', '2024-12-20', 'v', 2, 'closed', 7),
+                (4, '[]', '[]', '2024-12-08', 'v', 2, 'open', NULL);",
+        )
+        .await
+        .unwrap();
+
+        Migrator::up(&db, Some(1)).await.unwrap();
+
+        let estados = db
+            .query_all(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT state FROM ballot ORDER BY id",
+            ))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.try_get::<String>("", "state").unwrap())
+            .collect::<Vec<_>>();
+        // Semana 1 apurada; semana 2 não, mas o jogo 7 sim
+        assert_eq!(estados, vec!["computed", "submitted", "computed", "generated"]);
+    }
+}
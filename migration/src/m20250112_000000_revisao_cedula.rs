@@ -0,0 +1,54 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Guarda o voto anterior cada vez que um jogador reabre a cédula
+        manager
+            .create_table(
+                Table::create()
+                    .table(RevisaoCedula::Table)
+                    .if_not_exists()
+                    .col(pk_auto(RevisaoCedula::Id))
+                    .col(integer(RevisaoCedula::BallotId))
+                    .col(json(RevisaoCedula::Vote))
+                    .col(timestamp(RevisaoCedula::CriadoEm))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_revisao_cedula_ballot_id")
+                            .from(RevisaoCedula::Table, RevisaoCedula::BallotId)
+                            .to(Ballot::Table, Ballot::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RevisaoCedula::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Ballot {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum RevisaoCedula {
+    Table,
+    Id,
+    BallotId,
+    Vote,
+    CriadoEm,
+}
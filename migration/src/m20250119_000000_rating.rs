@@ -0,0 +1,103 @@
+use crate::m20241020_003335_create_jogo_e_jogador::Jogador;
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Rating::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Rating::Id))
+                    .col(integer(Rating::JogadorId))
+                    .col(integer(Rating::Temporada))
+                    .col(double(Rating::Mu))
+                    .col(double(Rating::Sigma))
+                    .col(integer(Rating::Partidas))
+                    .col(timestamp(Rating::AtualizadoEm))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_rating_jogador_id")
+                            .from(Rating::Table, Rating::JogadorId)
+                            .to(Jogador::Table, Jogador::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Um rating por jogador em cada temporada
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_rating_jogador_temporada")
+                    .table(Rating::Table)
+                    .col(Rating::JogadorId)
+                    .col(Rating::Temporada)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Cédulas que já entraram no rating, para a atualização incremental
+        manager
+            .create_table(
+                Table::create()
+                    .table(RatingCedula::Table)
+                    .if_not_exists()
+                    .col(pk_auto(RatingCedula::Id))
+                    .col(integer_uniq(RatingCedula::BallotId))
+                    .col(timestamp(RatingCedula::ProcessadoEm))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_rating_cedula_ballot_id")
+                            .from(RatingCedula::Table, RatingCedula::BallotId)
+                            .to(Ballot::Table, Ballot::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RatingCedula::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Rating::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Ballot {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Rating {
+    Table,
+    Id,
+    JogadorId,
+    Temporada,
+    Mu,
+    Sigma,
+    Partidas,
+    AtualizadoEm,
+}
+
+#[derive(DeriveIden)]
+enum RatingCedula {
+    Table,
+    Id,
+    BallotId,
+    ProcessadoEm,
+}
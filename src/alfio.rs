@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Pool, Postgres};
+
+use crate::error::{Error, Result};
+
+/// Descobre quais ids do alf.io correspondem a cada jogo, em vez de fixar o
+/// evento e o campo da camisa no código.
+///
+/// O evento de um jogo é o único evento que começa no dia do jogo, e o campo
+/// da camisa é o campo extra do evento com o nome configurado. O evento é
+/// procurado toda vez, porque outro evento criado depois no mesmo dia torna a
+/// escolha ambígua; o campo fica em cache por evento, já que os campos não
+/// mudam depois que o evento é criado.
+pub struct AlfioResolver {
+    campo_camisa: String,
+    campos: Mutex<HashMap<i32, i64>>,
+}
+
+impl AlfioResolver {
+    pub fn new(campo_camisa: impl Into<String>) -> Self {
+        Self {
+            campo_camisa: campo_camisa.into(),
+            campos: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Usa `ALFIO_CAMPO_CAMISA` como nome do campo, ou `camisa`.
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("ALFIO_CAMPO_CAMISA").unwrap_or_else(|_| "camisa".to_string()))
+    }
+
+    /// Evento do alf.io que começa entre `inicio` e `fim`, o dia local `dia`.
+    pub async fn evento(
+        &self,
+        db: &Pool<Postgres>,
+        dia: NaiveDate,
+        inicio: DateTime<Utc>,
+        fim: DateTime<Utc>,
+    ) -> Result<i32> {
+        let eventos = sqlx::query_as!(
+            Candidato::<i32>,
+            "SELECT id, short_name AS nome FROM event WHERE start_ts >= $1 AND start_ts < $2 ORDER BY id",
+            inicio,
+            fim
+        )
+        .fetch_all(db)
+        .await?;
+
+        let id = evento_do_dia(&eventos, dia)?;
+        tracing::debug!("alf.io event for {} is {}", dia, id);
+        Ok(id)
+    }
+
+    /// Campo extra do evento onde o jogador informa o nome da camisa.
+    pub async fn campo_camisa(&self, db: &Pool<Postgres>, event_id: i32) -> Result<i64> {
+        if let Some(id) = self.campos.lock().unwrap().get(&event_id) {
+            return Ok(*id);
+        }
+
+        let campos = sqlx::query_as!(
+            Candidato::<i64>,
+            "SELECT id, field_name AS nome FROM ticket_field_configuration
+            WHERE event_id_fk = $1 ORDER BY id",
+            event_id
+        )
+        .fetch_all(db)
+        .await?;
+
+        let id = campo_do_evento(&campos, event_id, &self.campo_camisa)?;
+        tracing::info!("alf.io shirt field for event {} is {}", event_id, id);
+        self.campos.lock().unwrap().insert(event_id, id);
+        Ok(id)
+    }
+}
+
+/// Um evento ou campo do alf.io que pode ser o procurado.
+struct Candidato<T> {
+    id: T,
+    nome: String,
+}
+
+/// O evento entre os que começam no dia; nenhum ou mais de um é erro.
+fn evento_do_dia(eventos: &[Candidato<i32>], dia: NaiveDate) -> Result<i32> {
+    unico(eventos, || format!("alf.io event starting on {}", dia.format("%d/%m/%Y")))
+}
+
+/// O campo com o nome dado, sem diferenciar maiúsculas, entre os campos extras
+/// do evento; nenhum ou mais de um é erro.
+fn campo_do_evento(campos: &[Candidato<i64>], event_id: i32, nome: &str) -> Result<i64> {
+    let nome_minusculo = nome.to_lowercase();
+    unico(
+        campos.iter().filter(|c| c.nome.to_lowercase() == nome_minusculo),
+        || format!("field named {:?} in alf.io event {}", nome, event_id),
+    )
+}
+
+/// O id do único candidato, senão um [`Error::AlfioMapping`] dizendo o que
+/// era `procurado`.
+fn unico<'a, T: Copy + std::fmt::Display + 'a>(
+    candidatos: impl IntoIterator<Item = &'a Candidato<T>>,
+    procurado: impl FnOnce() -> String,
+) -> Result<T> {
+    match candidatos.into_iter().collect::<Vec<_>>().as_slice() {
+        [candidato] => Ok(candidato.id),
+        [] => Err(Error::AlfioMapping(format!("no {}", procurado()))),
+        candidatos => Err(Error::AlfioMapping(format!(
+            "more than one {}: {}",
+            procurado(),
+            descreve(candidatos)
+        ))),
+    }
+}
+
+fn descreve<T: std::fmt::Display>(candidatos: &[&Candidato<T>]) -> String {
+    candidatos
+        .iter()
+        .map(|c| format!("{} (id {})", c.nome, c.id))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidato<T>(id: T, nome: &str) -> Candidato<T> {
+        Candidato {
+            id,
+            nome: nome.to_string(),
+        }
+    }
+
+    fn dia() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, 14).unwrap()
+    }
+
+    fn mensagem<T: std::fmt::Debug>(resultado: Result<T>) -> String {
+        match resultado {
+            Err(Error::AlfioMapping(msg)) => msg,
+            outro => panic!("expected AlfioMapping, got {outro:?}"),
+        }
+    }
+
+    #[test]
+    fn escolhe_o_unico_evento_do_dia() {
+        assert_eq!(evento_do_dia(&[candidato(7, "Fute")], dia()).unwrap(), 7);
+    }
+
+    #[test]
+    fn dia_sem_evento_e_erro() {
+        let msg = mensagem(evento_do_dia(&[], dia()));
+        assert!(msg.starts_with("no alf.io event starting on 14/03/2025"), "{msg}");
+    }
+
+    #[test]
+    fn dia_com_dois_eventos_e_ambiguo() {
+        let eventos = [candidato(7, "Fute"), candidato(9, "Fute extra")];
+
+        let msg = mensagem(evento_do_dia(&eventos, dia()));
+
+        assert!(msg.starts_with("more than one"), "{msg}");
+        assert!(msg.contains("Fute (id 7)") && msg.contains("Fute extra (id 9)"), "{msg}");
+    }
+
+    #[test]
+    fn campo_da_camisa_ignora_maiusculas() {
+        let campos = [candidato(1, "telefone"), candidato(2, "Camisa")];
+        assert_eq!(campo_do_evento(&campos, 7, "camisa").unwrap(), 2);
+    }
+
+    #[test]
+    fn campo_da_camisa_ausente_ou_repetido_e_erro() {
+        let msg = mensagem(campo_do_evento(&[candidato(1, "telefone")], 7, "camisa"));
+        assert!(msg.contains("\"camisa\"") && msg.contains("event 7"), "{msg}");
+
+        let campos = [candidato(1, "camisa"), candidato(2, "CAMISA")];
+        assert!(mensagem(campo_do_evento(&campos, 7, "camisa")).starts_with("more than one"));
+    }
+}
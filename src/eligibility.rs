@@ -0,0 +1,669 @@
+use std::fmt;
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+
+use crate::entities::{prelude::*, *};
+use crate::error::Result;
+use crate::timings::{self, get_end_elegible_check};
+use crate::{inscricoes, jogos, AppState};
+
+/// Exige presença em pelo menos `jogos` dos `ultimos` eventos da janela.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PresencaMinima {
+    pub jogos: usize,
+    pub ultimos: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Banimento {
+    pub email: String,
+    #[serde(default)]
+    pub motivo: Option<String>,
+}
+
+/// Regras de elegibilidade, lidas de um arquivo de configuração.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Regras {
+    /// Quantas semanas antes da votação semanal uma inscrição ainda conta.
+    pub janela_semanas: i64,
+    pub presenca_minima: Option<PresencaMinima>,
+    /// Jogadores que não podem ser votados.
+    pub banidos: Vec<Banimento>,
+    /// Emails de convidados, que podem ser votados mas não votam.
+    pub convidados: Vec<String>,
+}
+
+impl Default for Regras {
+    fn default() -> Self {
+        Regras {
+            janela_semanas: 4,
+            presenca_minima: None,
+            banidos: vec![],
+            convidados: vec![],
+        }
+    }
+}
+
+impl Regras {
+    /// Lê as regras do arquivo JSON em `ELIGIBILITY_FILE`, ou usa o padrão
+    /// (qualquer inscrição nas últimas 4 semanas). Por exemplo:
+    ///
+    /// ```json
+    /// {"janela_semanas": 6,
+    ///  "presenca_minima": {"jogos": 2, "ultimos": 4},
+    ///  "banidos": [{"email": "fulano@inf.ufrgs.br", "motivo": "Suspenso até março"}],
+    ///  "convidados": ["visitante@gmail.com"]}
+    /// ```
+    pub fn from_env() -> anyhow::Result<Self> {
+        let regras = match std::env::var("ELIGIBILITY_FILE") {
+            Ok(path) => {
+                let content = std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+                serde_json::from_str(&content)?
+            }
+            Err(_) => Regras::default(),
+        };
+        regras.validate()?;
+        Ok(regras)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.janela_semanas <= 0 {
+            anyhow::bail!("The eligibility window must be at least one week");
+        }
+        if let Some(p) = &self.presenca_minima {
+            if p.jogos == 0 || p.jogos > p.ultimos {
+                anyhow::bail!("The minimum attendance must be between 1 and the number of games");
+            }
+        }
+        Ok(())
+    }
+
+    fn banimento(&self, email: &str) -> Option<&Banimento> {
+        self.banidos
+            .iter()
+            .find(|b| b.email.eq_ignore_ascii_case(email))
+    }
+
+    fn convidado(&self, email: &str) -> bool {
+        self.convidados.iter().any(|c| c.eq_ignore_ascii_case(email))
+    }
+}
+
+lazy_static! {
+    pub static ref REGRAS: Regras =
+        Regras::from_env().expect("Invalid eligibility configuration");
+}
+
+/// Para qual votação a elegibilidade é calculada.
+#[derive(Debug, Clone, Copy)]
+pub enum Escopo<'a> {
+    /// Votação semanal que acontece em `date`, olhando as últimas semanas.
+    Semana(DateTime<Utc>),
+    /// Votação de um jogo, olhando só o dia do jogo.
+    Jogo(&'a jogo::Model),
+}
+
+/// Por que um jogador pode ser votado.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "tipo", rename_all = "snake_case")]
+pub enum Motivo {
+    /// Se inscreveu em um evento dentro da janela.
+    Ingresso { evento: String, data: DateTime<Utc> },
+    /// Foi colocado na lista extra por um admin.
+    ListaExtra { id: i32, data: NaiveDateTime },
+    /// Estava confirmado na lista do jogo.
+    ListaDoJogo { jogo: String },
+}
+
+/// Por que um jogador não pode ser votado.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "tipo", rename_all = "snake_case")]
+pub enum Impedimento {
+    /// Nenhuma inscrição nem lista extra dentro da janela.
+    SemInscricao { inicio: DateTime<Utc>, fim: DateTime<Utc> },
+    /// Jogou menos que o exigido por [`PresencaMinima`].
+    PresencaInsuficiente {
+        presencas: usize,
+        minimo: usize,
+        ultimos: usize,
+    },
+    Banido { motivo: Option<String> },
+}
+
+fn format_local(date: DateTime<Utc>) -> String {
+    date.with_timezone(&timings::timezone())
+        .format("%d/%m/%Y %H:%M")
+        .to_string()
+}
+
+impl fmt::Display for Motivo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Motivo::Ingresso { evento, data } => {
+                write!(f, "Inscrito em {} ({})", evento, format_local(*data))
+            }
+            Motivo::ListaExtra { id, data } => write!(
+                f,
+                "Lista extra #{} em {}",
+                id,
+                format_local(data.and_utc())
+            ),
+            Motivo::ListaDoJogo { jogo } => write!(f, "Confirmado na lista do jogo {}", jogo),
+        }
+    }
+}
+
+impl fmt::Display for Impedimento {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Impedimento::SemInscricao { inicio, fim } => write!(
+                f,
+                "Nenhuma inscrição nem lista extra entre {} e {}",
+                format_local(*inicio),
+                format_local(*fim)
+            ),
+            Impedimento::PresencaInsuficiente {
+                presencas,
+                minimo,
+                ultimos,
+            } => write!(
+                f,
+                "Jogou {} dos últimos {} jogos, o mínimo é {}",
+                presencas, ultimos, minimo
+            ),
+            Impedimento::Banido { motivo: Some(motivo) } => write!(f, "Banido: {}", motivo),
+            Impedimento::Banido { motivo: None } => write!(f, "Banido"),
+        }
+    }
+}
+
+/// Por que um jogador não pode votar.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "tipo", rename_all = "snake_case")]
+pub enum ImpedimentoVoto {
+    /// Não jogou na semana ou no jogo da votação.
+    NaoJogou { inicio: DateTime<Utc>, fim: DateTime<Utc> },
+    Convidado,
+    Banido,
+}
+
+impl fmt::Display for ImpedimentoVoto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImpedimentoVoto::NaoJogou { inicio, fim } => write!(
+                f,
+                "Não jogou entre {} e {}",
+                format_local(*inicio),
+                format_local(*fim)
+            ),
+            ImpedimentoVoto::Convidado => write!(f, "Convidados não votam"),
+            ImpedimentoVoto::Banido => write!(f, "Banidos não votam"),
+        }
+    }
+}
+
+/// Resultado da elegibilidade de um jogador, com as razões.
+#[derive(Debug, Clone)]
+pub struct Avaliacao {
+    pub jogador: jogador::Model,
+    pub motivos: Vec<Motivo>,
+    pub impedimentos: Vec<Impedimento>,
+    /// Convidados podem ser votados, mas não votam.
+    pub convidado: bool,
+    pub impedimentos_voto: Vec<ImpedimentoVoto>,
+}
+
+impl Avaliacao {
+    pub fn elegivel(&self) -> bool {
+        self.impedimentos.is_empty()
+    }
+
+    pub fn pode_votar(&self) -> bool {
+        self.impedimentos_voto.is_empty()
+    }
+}
+
+/// Janela de datas em que uma inscrição conta para o escopo.
+pub fn janela(escopo: Escopo) -> (DateTime<Utc>, DateTime<Utc>) {
+    janela_com(&REGRAS, escopo)
+}
+
+fn janela_com(regras: &Regras, escopo: Escopo) -> (DateTime<Utc>, DateTime<Utc>) {
+    match escopo {
+        Escopo::Semana(date) => {
+            let fim = get_end_elegible_check(date);
+            (fim - Duration::weeks(regras.janela_semanas), fim)
+        }
+        Escopo::Jogo(jogo) => jogos::dia_do_jogo(jogo),
+    }
+}
+
+/// Janela em que o jogador precisa ter jogado para votar: a última semana na
+/// votação semanal, o dia do jogo na votação de um jogo.
+pub fn janela_votante(escopo: Escopo) -> (DateTime<Utc>, DateTime<Utc>) {
+    match escopo {
+        Escopo::Semana(date) => {
+            let fim = get_end_elegible_check(date);
+            (fim - Duration::weeks(1), fim)
+        }
+        Escopo::Jogo(jogo) => jogos::dia_do_jogo(jogo),
+    }
+}
+
+/// Avalia todos os jogadores cadastrados com as regras configuradas.
+pub async fn avaliar(state: &AppState, escopo: Escopo<'_>) -> Result<Vec<Avaliacao>> {
+    avaliar_com(state, escopo, &REGRAS).await
+}
+
+pub async fn avaliar_com(
+    state: &AppState,
+    escopo: Escopo<'_>,
+    regras: &Regras,
+) -> Result<Vec<Avaliacao>> {
+    let db = &state.db;
+    let (inicio, fim) = janela_com(regras, escopo);
+    let (inicio_voto, fim_voto) = janela_votante(escopo);
+    // A presença mínima sempre olha as semanas da janela, mesmo na votação de
+    // um jogo, que só conta inscrições do dia.
+    let inicio_presenca = inicio.min(fim - Duration::weeks(regras.janela_semanas));
+
+    // Contas desativadas ou sem email verificado não entram na votação
+    let all_players: Vec<jogador::Model> = Jogador::find()
+        .filter(jogador::Column::Ativo.eq(true))
+        .order_by_asc(jogador::Column::Nome)
+        .all(db)
+        .await?;
+
+    let historico = state.roster.ingressos(inicio_presenca, fim).await?;
+    let ingressos = historico
+        .iter()
+        .filter(|x| x.data >= inicio)
+        .collect::<Vec<_>>();
+
+    let historico_extra = ListaExtra::find()
+        .filter(lista_extra::Column::Data.gt(inicio_presenca))
+        .filter(lista_extra::Column::Data.lt(fim))
+        .all(db)
+        .await?;
+    let extra_players = historico_extra
+        .iter()
+        .filter(|x| x.data.and_utc() > inicio)
+        .collect::<Vec<_>>();
+
+    // Os últimos eventos da janela, do mais recente para o mais antigo
+    let ultimos_eventos = historico
+        .iter()
+        .map(|x| (x.data, x.evento.as_str()))
+        .unique()
+        .sorted()
+        .rev()
+        .take(regras.presenca_minima.as_ref().map_or(0, |p| p.ultimos))
+        .collect::<Vec<_>>();
+
+    let confirmados = match escopo {
+        Escopo::Jogo(jogo) => inscricoes::lista_do_jogo(db, jogo)
+            .await?
+            .confirmados
+            .into_iter()
+            .map(|x| x.jogador_id)
+            .collect(),
+        Escopo::Semana(_) => vec![],
+    };
+
+    Ok(all_players
+        .into_iter()
+        .map(|jogador| {
+            let mut motivos = ingressos
+                .iter()
+                .filter(|x| x.email == jogador.email)
+                .map(|x| Motivo::Ingresso {
+                    evento: x.evento.clone(),
+                    data: x.data,
+                })
+                .collect::<Vec<_>>();
+            motivos.extend(
+                extra_players
+                    .iter()
+                    .filter(|x| x.jogador_id == jogador.id)
+                    .map(|x| Motivo::ListaExtra {
+                        id: x.id,
+                        data: x.data,
+                    }),
+            );
+            if let Escopo::Jogo(jogo) = escopo {
+                if confirmados.contains(&jogador.id) {
+                    motivos.push(Motivo::ListaDoJogo {
+                        jogo: jogo.nome.clone(),
+                    });
+                }
+            }
+
+            let mut impedimentos = vec![];
+            if motivos.is_empty() {
+                impedimentos.push(Impedimento::SemInscricao { inicio, fim });
+            } else if let Some(presenca) = &regras.presenca_minima {
+                // Cada entrada na lista extra e a confirmação no jogo contam
+                // como um jogo a mais
+                let em_eventos = ultimos_eventos
+                    .iter()
+                    .filter(|(data, evento)| {
+                        historico.iter().any(|x| {
+                            x.email == jogador.email && x.data == *data && x.evento == *evento
+                        })
+                    })
+                    .count();
+                let na_lista_extra = historico_extra
+                    .iter()
+                    .filter(|x| x.jogador_id == jogador.id)
+                    .count();
+                let no_jogo = usize::from(confirmados.contains(&jogador.id));
+                let presencas = (em_eventos + na_lista_extra + no_jogo).min(presenca.ultimos);
+                if presencas < presenca.jogos {
+                    impedimentos.push(Impedimento::PresencaInsuficiente {
+                        presencas,
+                        minimo: presenca.jogos,
+                        ultimos: presenca.ultimos,
+                    });
+                }
+            }
+            if let Some(banimento) = regras.banimento(&jogador.email) {
+                impedimentos.push(Impedimento::Banido {
+                    motivo: banimento.motivo.clone(),
+                });
+            }
+            let convidado = regras.convidado(&jogador.email);
+
+            let jogou = motivos.iter().any(|m| match m {
+                Motivo::Ingresso { data, .. } => *data >= inicio_voto && *data < fim_voto,
+                Motivo::ListaExtra { data, .. } => {
+                    data.and_utc() > inicio_voto && data.and_utc() < fim_voto
+                }
+                Motivo::ListaDoJogo { .. } => true,
+            });
+            let mut impedimentos_voto = vec![];
+            if !jogou {
+                impedimentos_voto.push(ImpedimentoVoto::NaoJogou {
+                    inicio: inicio_voto,
+                    fim: fim_voto,
+                });
+            }
+            if convidado {
+                impedimentos_voto.push(ImpedimentoVoto::Convidado);
+            }
+            if impedimentos
+                .iter()
+                .any(|i| matches!(i, Impedimento::Banido { .. }))
+            {
+                impedimentos_voto.push(ImpedimentoVoto::Banido);
+            }
+
+            Avaliacao {
+                jogador,
+                motivos,
+                impedimentos,
+                convidado,
+                impedimentos_voto,
+            }
+        })
+        .collect())
+}
+
+/// Jogadores que podem ser votados.
+pub async fn elegiveis(state: &AppState, escopo: Escopo<'_>) -> Result<Vec<jogador::Model>> {
+    let elegible_players = avaliar(state, escopo)
+        .await?
+        .into_iter()
+        .filter(Avaliacao::elegivel)
+        .map(|x| x.jogador)
+        .collect::<Vec<_>>();
+
+    tracing::info!(
+        "Elegible players({}): {:?}",
+        elegible_players.len(),
+        elegible_players.iter().map(|x| x.nome.to_owned()).collect::<Vec<String>>()
+    );
+
+    Ok(elegible_players)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use sea_orm::{ActiveModelTrait, ActiveValue};
+
+    use super::*;
+    use crate::fixtures::{cria_jogador, cria_jogo, state_com};
+    use crate::roster::{Ingresso, MemoryRoster};
+
+    fn ingresso(nome: &str, data: DateTime<Utc>) -> Ingresso {
+        Ingresso {
+            email: format!("{nome}@inf.ufrgs.br"),
+            evento: "Fute".to_string(),
+            data,
+        }
+    }
+
+    fn nomes(players: &[jogador::Model]) -> Vec<String> {
+        let mut nomes = players.iter().map(|p| p.nome.clone()).collect::<Vec<_>>();
+        nomes.sort();
+        nomes
+    }
+
+    #[tokio::test]
+    async fn elegiveis_vem_do_roster_e_da_lista_extra() {
+        let now = Utc::now();
+        let dentro = janela(Escopo::Semana(now)).0 + Duration::days(3);
+        let state = state_com(MemoryRoster {
+            ingressos: vec![
+                ingresso("ana", dentro),
+                ingresso("caio", dentro - Duration::weeks(10)),
+            ],
+            ..Default::default()
+        })
+        .await;
+        cria_jogador(&state.db, "ana").await;
+        let bia = cria_jogador(&state.db, "bia").await;
+        cria_jogador(&state.db, "caio").await;
+        lista_extra::ActiveModel {
+            jogador_id: ActiveValue::Set(bia.id),
+            data: ActiveValue::Set(dentro.naive_utc()),
+            ..Default::default()
+        }
+        .insert(&state.db)
+        .await
+        .unwrap();
+
+        let players = elegiveis(&state, Escopo::Semana(now)).await.unwrap();
+
+        assert_eq!(nomes(&players), vec!["ana", "bia"]);
+    }
+
+    #[tokio::test]
+    async fn jogador_inativo_nao_e_avaliado() {
+        let now = Utc::now();
+        let dentro = janela(Escopo::Semana(now)).0 + Duration::days(3);
+        let state = state_com(MemoryRoster {
+            ingressos: vec![ingresso("ana", dentro), ingresso("bia", dentro)],
+            ..Default::default()
+        })
+        .await;
+        cria_jogador(&state.db, "ana").await;
+        let bia = cria_jogador(&state.db, "bia").await;
+        jogador::ActiveModel {
+            ativo: ActiveValue::Set(false),
+            ..bia.into()
+        }
+        .update(&state.db)
+        .await
+        .unwrap();
+
+        let players = elegiveis(&state, Escopo::Semana(now)).await.unwrap();
+
+        assert_eq!(nomes(&players), vec!["ana"]);
+        let avaliacoes = avaliar(&state, Escopo::Semana(now)).await.unwrap();
+        assert_eq!(avaliacoes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn avaliacao_explica_cada_jogador() {
+        let now = Utc::now();
+        let dentro = janela(Escopo::Semana(now)).0 + Duration::days(3);
+        let state = state_com(MemoryRoster {
+            ingressos: vec![ingresso("ana", dentro)],
+            ..Default::default()
+        })
+        .await;
+        cria_jogador(&state.db, "ana").await;
+        cria_jogador(&state.db, "bia").await;
+
+        let avaliacoes = avaliar(&state, Escopo::Semana(now)).await.unwrap();
+
+        let ana = &avaliacoes[0];
+        assert!(ana.elegivel());
+        assert_eq!(
+            ana.motivos,
+            vec![Motivo::Ingresso {
+                evento: "Fute".to_string(),
+                data: dentro
+            }]
+        );
+        let bia = &avaliacoes[1];
+        assert!(!bia.elegivel());
+        assert!(bia.motivos.is_empty());
+        assert!(matches!(bia.impedimentos[..], [Impedimento::SemInscricao { .. }]));
+    }
+
+    #[tokio::test]
+    async fn elegiveis_do_jogo_incluem_confirmados_na_lista() {
+        let state = state_com(MemoryRoster::default()).await;
+        let ana = cria_jogador(&state.db, "ana").await;
+        let bia = cria_jogador(&state.db, "bia").await;
+        let jogo = cria_jogo(&state.db, 1).await;
+        inscricoes::inscrever(&state.db, jogo.id, ana.id).await.unwrap();
+        // Fica na lista de espera, então não jogou
+        inscricoes::inscrever(&state.db, jogo.id, bia.id).await.unwrap();
+
+        let players = elegiveis(&state, Escopo::Jogo(&jogo)).await.unwrap();
+
+        assert_eq!(nomes(&players), vec!["ana"]);
+    }
+
+    #[tokio::test]
+    async fn presenca_minima_conta_os_ultimos_eventos() {
+        let now = Utc::now();
+        let fim = janela(Escopo::Semana(now)).1;
+        let regras = Regras {
+            presenca_minima: Some(PresencaMinima { jogos: 2, ultimos: 3 }),
+            ..Default::default()
+        };
+        let semana = |n| fim - Duration::weeks(n) + Duration::days(1);
+        let state = state_com(MemoryRoster {
+            ingressos: vec![
+                ingresso("ana", semana(1)),
+                ingresso("ana", semana(2)),
+                ingresso("bia", semana(1)),
+                // Fora dos últimos 3 eventos
+                ingresso("bia", semana(4)),
+                ingresso("caio", semana(3)),
+            ],
+            ..Default::default()
+        })
+        .await;
+        cria_jogador(&state.db, "ana").await;
+        cria_jogador(&state.db, "bia").await;
+        cria_jogador(&state.db, "caio").await;
+
+        let avaliacoes = avaliar_com(&state, Escopo::Semana(now), &regras)
+            .await
+            .unwrap();
+
+        assert!(avaliacoes[0].elegivel());
+        assert_eq!(
+            avaliacoes[1].impedimentos,
+            vec![Impedimento::PresencaInsuficiente {
+                presencas: 1,
+                minimo: 2,
+                ultimos: 3
+            }]
+        );
+        assert!(!avaliacoes[2].elegivel());
+    }
+
+    #[tokio::test]
+    async fn banidos_e_convidados() {
+        let now = Utc::now();
+        let dentro = janela(Escopo::Semana(now)).0 + Duration::days(3);
+        let regras = Regras {
+            banidos: vec![Banimento {
+                email: "ANA@inf.ufrgs.br".to_string(),
+                motivo: Some("Suspenso".to_string()),
+            }],
+            convidados: vec!["bia@inf.ufrgs.br".to_string()],
+            ..Default::default()
+        };
+        let state = state_com(MemoryRoster {
+            ingressos: vec![ingresso("ana", dentro), ingresso("bia", dentro)],
+            ..Default::default()
+        })
+        .await;
+        cria_jogador(&state.db, "ana").await;
+        cria_jogador(&state.db, "bia").await;
+
+        let avaliacoes = avaliar_com(&state, Escopo::Semana(now), &regras)
+            .await
+            .unwrap();
+
+        let ana = &avaliacoes[0];
+        assert!(!ana.elegivel());
+        assert!(!ana.pode_votar());
+        assert_eq!(
+            ana.impedimentos,
+            vec![Impedimento::Banido {
+                motivo: Some("Suspenso".to_string())
+            }]
+        );
+        let bia = &avaliacoes[1];
+        assert!(bia.elegivel());
+        assert!(!bia.pode_votar());
+    }
+
+    #[test]
+    fn regras_invalidas() {
+        let regras: Regras =
+            serde_json::from_str(r#"{"presenca_minima": {"jogos": 3, "ultimos": 2}}"#).unwrap();
+        assert!(regras.validate().is_err());
+        assert!(Regras::default().validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn so_vota_quem_jogou_na_semana() {
+        let now = Utc::now();
+        let (inicio, fim) = janela_votante(Escopo::Semana(now));
+        let state = state_com(MemoryRoster {
+            ingressos: vec![
+                ingresso("ana", inicio + Duration::days(1)),
+                ingresso("bia", fim - Duration::weeks(3)),
+            ],
+            ..Default::default()
+        })
+        .await;
+        cria_jogador(&state.db, "ana").await;
+        cria_jogador(&state.db, "bia").await;
+
+        let avaliacoes = avaliar(&state, Escopo::Semana(now)).await.unwrap();
+
+        assert!(avaliacoes[0].pode_votar());
+        let bia = &avaliacoes[1];
+        assert!(bia.elegivel());
+        assert_eq!(
+            bia.impedimentos_voto,
+            vec![ImpedimentoVoto::NaoJogou { inicio, fim }]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.0-rc.5
 
+use super::sea_orm_active_enums::ApuracaoState;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -11,7 +12,7 @@ pub struct Model {
     pub week_id: i32,
     #[sea_orm(unique)]
     pub random_id: String,
-    pub state: String,
+    pub state: ApuracaoState,
     pub results: Json,
 }
 
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.0-rc.5
 
+use super::sea_orm_active_enums::BallotState;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -12,11 +13,35 @@ pub struct Model {
     pub date: DateTimeUtc,
     pub voter: String,
     pub fute_id: i32,
-    #[sea_orm(column_type = "custom(\"enum_text\")")]
-    pub state: String,
+    pub state: BallotState,
+    pub jogo_id: Option<i32>,
+    pub tamanho: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::jogo::Entity",
+        from = "Column::JogoId",
+        to = "super::jogo::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Jogo,
+    #[sea_orm(has_many = "super::revisao_cedula::Entity")]
+    RevisaoCedula,
+}
+
+impl Related<super::jogo::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Jogo.def()
+    }
+}
+
+impl Related<super::revisao_cedula::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RevisaoCedula.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
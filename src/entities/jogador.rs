@@ -14,12 +14,23 @@ pub struct Model {
     pub admin: bool,
     #[sea_orm(column_type = "Blob", nullable)]
     pub imagem: Option<Vec<u8>>,
+    pub ativo: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::inscricao::Entity")]
+    Inscricao,
     #[sea_orm(has_many = "super::lista_extra::Entity")]
     ListaExtra,
+    #[sea_orm(has_many = "super::token::Entity")]
+    Token,
+}
+
+impl Related<super::inscricao::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Inscricao.def()
+    }
 }
 
 impl Related<super::lista_extra::Entity> for Entity {
@@ -28,4 +39,10 @@ impl Related<super::lista_extra::Entity> for Entity {
     }
 }
 
+impl Related<super::token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Token.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -10,9 +10,38 @@ pub struct Model {
     pub nome: String,
     pub data: Date,
     pub local: String,
+    pub hora: Time,
+    pub capacidade: i32,
+    pub cancelado: bool,
+    pub tamanho_cedula: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_one = "super::apuracao_jogo::Entity")]
+    ApuracaoJogo,
+    #[sea_orm(has_many = "super::ballot::Entity")]
+    Ballot,
+    #[sea_orm(has_many = "super::inscricao::Entity")]
+    Inscricao,
+}
+
+impl Related<super::apuracao_jogo::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApuracaoJogo.def()
+    }
+}
+
+impl Related<super::ballot::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Ballot.def()
+    }
+}
+
+impl Related<super::inscricao::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Inscricao.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
@@ -3,7 +3,14 @@
 pub mod prelude;
 
 pub mod apuracao;
+pub mod apuracao_jogo;
 pub mod ballot;
+pub mod inscricao;
 pub mod jogador;
 pub mod jogo;
 pub mod lista_extra;
+pub mod rating;
+pub mod rating_cedula;
+pub mod revisao_cedula;
+pub mod sea_orm_active_enums;
+pub mod token;
@@ -1,7 +1,12 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.0-rc.5
 
 pub use super::apuracao::Entity as Apuracao;
+pub use super::apuracao_jogo::Entity as ApuracaoJogo;
 pub use super::ballot::Entity as Ballot;
+pub use super::inscricao::Entity as Inscricao;
 pub use super::jogador::Entity as Jogador;
 pub use super::jogo::Entity as Jogo;
 pub use super::lista_extra::Entity as ListaExtra;
+pub use super::rating::Entity as Rating;
+pub use super::rating_cedula::Entity as RatingCedula;
+pub use super::token::Entity as Token;
@@ -17,11 +17,21 @@ pub enum Error {
     JsonError(#[from] serde_json::Error),
     #[error("Integer Parse error: {0}")]
     ParseError(#[from] std::num::ParseIntError),
+    #[error("You need to be logged in to access this page")]
+    Unauthorized,
+    #[error("Access denied: {0}")]
+    Forbidden(String),
+    #[error("Could not find the alf.io data for this game: {0}")]
+    AlfioMapping(String),
 }
 
 impl ResponseError for Error {
     fn status_code(&self) -> actix_web::http::StatusCode {
-        actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
+        match self {
+            Error::Unauthorized => actix_web::http::StatusCode::UNAUTHORIZED,
+            Error::Forbidden(_) => actix_web::http::StatusCode::FORBIDDEN,
+            _ => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
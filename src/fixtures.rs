@@ -0,0 +1,108 @@
+//! Modelos e banco em memória compartilhados pelos testes.
+
+use std::sync::Arc;
+
+use chrono::{NaiveTime, Utc};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ConnectOptions, Database, DatabaseConnection, IntoActiveModel,
+};
+
+use crate::entities::sea_orm_active_enums::BallotState;
+use crate::entities::{ballot, jogador, jogo};
+use crate::roster::MemoryRoster;
+use crate::AppState;
+
+/// SQLite em memória com todas as migrações aplicadas.
+pub async fn banco() -> DatabaseConnection {
+    // Uma conexão só, senão cada conexão do pool vê um banco vazio diferente
+    let mut options = ConnectOptions::new("sqlite::memory:");
+    options.max_connections(1);
+    let db = Database::connect(options).await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+    db
+}
+
+pub async fn state_com(roster: MemoryRoster) -> AppState {
+    AppState {
+        roster: Arc::new(roster),
+        db: banco().await,
+        mailer: Arc::new(crate::mail::LogMailer),
+        public_url: String::new(),
+        sorteio: crate::sorteio::Sorteio::Uniforme,
+        scoring: Arc::new(crate::scoring::Atual),
+    }
+}
+
+pub fn jogador(id: i32) -> jogador::Model {
+    jogador::Model {
+        id,
+        nome: format!("Jogador {id}"),
+        apelido: format!("j{id}"),
+        email: format!("j{id}@inf.ufrgs.br"),
+        senha_hash: String::new(),
+        admin: false,
+        imagem: None,
+        ativo: true,
+    }
+}
+
+/// Jogadores com ids de 1 a `n`.
+pub fn jogadores(n: i32) -> Vec<jogador::Model> {
+    (1..=n).map(jogador).collect()
+}
+
+/// Grava um jogador ativo com email `{nome}@inf.ufrgs.br`.
+pub async fn cria_jogador(db: &DatabaseConnection, nome: &str) -> jogador::Model {
+    jogador::ActiveModel {
+        nome: ActiveValue::Set(nome.to_string()),
+        apelido: ActiveValue::Set(nome.to_string()),
+        email: ActiveValue::Set(format!("{nome}@inf.ufrgs.br")),
+        senha_hash: ActiveValue::Set(String::new()),
+        admin: ActiveValue::Set(false),
+        imagem: ActiveValue::Set(None),
+        ativo: ActiveValue::Set(true),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+}
+
+/// Grava um jogo de hoje às 19h.
+pub async fn cria_jogo(db: &DatabaseConnection, capacidade: i32) -> jogo::Model {
+    jogo::ActiveModel {
+        nome: ActiveValue::Set("Fute".to_string()),
+        data: ActiveValue::Set(Utc::now().date_naive()),
+        local: ActiveValue::Set("Quadra".to_string()),
+        hora: ActiveValue::Set(NaiveTime::from_hms_opt(19, 0, 0).unwrap()),
+        capacidade: ActiveValue::Set(capacidade),
+        cancelado: ActiveValue::Set(false),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap()
+}
+
+/// Cédula apurada da semana 1 com os candidatos `players`, do tamanho deles.
+pub fn cedula(players: &[i32], vote: &[i32]) -> ballot::Model {
+    ballot::Model {
+        id: 1,
+        players: serde_json::json!(players),
+        vote: serde_json::json!(vote),
+        date: Utc::now(),
+        voter: "voter@inf.ufrgs.br".to_string(),
+        fute_id: 1,
+        state: BallotState::Computed,
+        jogo_id: None,
+        tamanho: players.len() as i32,
+    }
+}
+
+/// Grava a cédula com um id novo.
+pub async fn cria_cedula(db: &DatabaseConnection, cedula: ballot::Model) -> ballot::Model {
+    let mut cedula = cedula.into_active_model();
+    cedula.id = ActiveValue::NotSet;
+    cedula.insert(db).await.unwrap()
+}
@@ -0,0 +1,68 @@
+use actix_identity::Identity;
+use actix_web::dev::Payload;
+use actix_web::web::Data;
+use actix_web::{FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use crate::entities::{prelude::*, *};
+use crate::error::{Error, Result};
+use crate::AppState;
+
+/// Jogador logado, buscado uma vez a partir do `Identity` da sessão.
+///
+/// Falha com 401 se não houver login válido ou a conta estiver inativa.
+/// Use `Option<CurrentUser>` em páginas que também atendem visitantes.
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub jogador::Model);
+
+/// Como [`CurrentUser`], mas falha com 403 se o jogador não for admin.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub jogador::Model);
+
+async fn find_user(state: Option<Data<AppState>>, email: Option<String>) -> Result<jogador::Model> {
+    let email = email.ok_or(Error::Unauthorized)?;
+    let state = state.ok_or(anyhow::anyhow!("AppState not configured"))?;
+    Jogador::find()
+        .filter(jogador::Column::Email.eq(email))
+        .filter(jogador::Column::Ativo.eq(true))
+        .one(&state.db)
+        .await?
+        .ok_or(Error::Unauthorized)
+}
+
+fn identity_email(req: &HttpRequest, payload: &mut Payload) -> Option<String> {
+    Identity::from_request(req, payload)
+        .into_inner()
+        .ok()
+        .and_then(|identity| identity.id().ok())
+}
+
+impl FromRequest for CurrentUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let email = identity_email(req, payload);
+        let state = req.app_data::<Data<AppState>>().cloned();
+        Box::pin(async move { Ok(CurrentUser(find_user(state, email).await?)) })
+    }
+}
+
+impl FromRequest for AdminUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let email = identity_email(req, payload);
+        let state = req.app_data::<Data<AppState>>().cloned();
+        Box::pin(async move {
+            let user = find_user(state, email).await?;
+            if !user.admin {
+                tracing::info!("User {} is not an admin", user.email);
+                return Err(Error::Forbidden("only admins can access this page".to_string()));
+            }
+            Ok(AdminUser(user))
+        })
+    }
+}
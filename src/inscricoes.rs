@@ -0,0 +1,237 @@
+use chrono::{DateTime, Utc};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter,
+    QueryOrder, TryInsertResult,
+};
+use serde::Serialize;
+
+use crate::entities::{prelude::*, *};
+use crate::error::Result;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Inscrito {
+    pub jogador_id: i32,
+    pub nome: String,
+    pub apelido: String,
+    pub email: String,
+    pub criado_em: DateTime<Utc>,
+}
+
+/// Lista de um jogo: os primeiros `capacidade` inscritos jogam, o resto fica
+/// na lista de espera na ordem em que se inscreveu.
+#[derive(Debug, Clone, Serialize)]
+pub struct ListaJogo {
+    pub confirmados: Vec<Inscrito>,
+    pub espera: Vec<Inscrito>,
+}
+
+impl ListaJogo {
+    pub fn posicao(&self, jogador_id: i32) -> Option<Posicao> {
+        if let Some(i) = self.confirmados.iter().position(|x| x.jogador_id == jogador_id) {
+            return Some(Posicao::Confirmado(i + 1));
+        }
+        self.espera
+            .iter()
+            .position(|x| x.jogador_id == jogador_id)
+            .map(|i| Posicao::Espera(i + 1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Posicao {
+    Confirmado(usize),
+    Espera(usize),
+}
+
+pub async fn lista_do_jogo(db: &DatabaseConnection, jogo: &jogo::Model) -> Result<ListaJogo> {
+    let inscritos = Inscricao::find()
+        .find_also_related(Jogador)
+        .filter(inscricao::Column::JogoId.eq(jogo.id))
+        .order_by_asc(inscricao::Column::CriadoEm)
+        .order_by_asc(inscricao::Column::Id)
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|(inscricao, jogador)| {
+            jogador.map(|j| Inscrito {
+                jogador_id: j.id,
+                nome: j.nome,
+                apelido: j.apelido,
+                email: j.email,
+                criado_em: inscricao.criado_em,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let capacidade = jogo.capacidade.max(0) as usize;
+    let mut confirmados = inscritos;
+    let espera = confirmados.split_off(capacidade.min(confirmados.len()));
+    Ok(ListaJogo { confirmados, espera })
+}
+
+/// Inscreve o jogador no fim da lista. Não faz nada se ele já estiver inscrito.
+pub async fn inscrever(db: &DatabaseConnection, jogo_id: i32, jogador_id: i32) -> Result<()> {
+    // O índice único decide entre duas inscrições simultâneas
+    let resultado = Inscricao::insert(inscricao::ActiveModel {
+        jogo_id: ActiveValue::Set(jogo_id),
+        jogador_id: ActiveValue::Set(jogador_id),
+        criado_em: ActiveValue::Set(Utc::now()),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::columns([inscricao::Column::JogoId, inscricao::Column::JogadorId])
+            .do_nothing()
+            .to_owned(),
+    )
+    .do_nothing()
+    .exec(db)
+    .await?;
+    if let TryInsertResult::Inserted(_) = resultado {
+        tracing::info!("Jogador {} joined jogo {}", jogador_id, jogo_id);
+    }
+    Ok(())
+}
+
+/// Remove o jogador da lista. Se ele estava confirmado, o primeiro da lista de
+/// espera assume a vaga e é retornado para ser avisado.
+pub async fn sair(
+    db: &DatabaseConnection,
+    jogo: &jogo::Model,
+    jogador_id: i32,
+) -> Result<Option<Inscrito>> {
+    let antes = lista_do_jogo(db, jogo).await?;
+
+    let inscricao = Inscricao::find()
+        .filter(inscricao::Column::JogoId.eq(jogo.id))
+        .filter(inscricao::Column::JogadorId.eq(jogador_id))
+        .one(db)
+        .await?;
+    let Some(inscricao) = inscricao else {
+        return Ok(None);
+    };
+    inscricao.delete(db).await?;
+    tracing::info!("Jogador {} left jogo {}", jogador_id, jogo.id);
+
+    match antes.posicao(jogador_id) {
+        Some(Posicao::Confirmado(_)) => {
+            let promovido = antes.espera.into_iter().next();
+            if let Some(p) = &promovido {
+                tracing::info!("Jogador {} promoted from waitlist of jogo {}", p.jogador_id, jogo.id);
+            }
+            Ok(promovido)
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{banco, cria_jogador, cria_jogo};
+    use chrono::Duration;
+
+    fn ids(inscritos: &[Inscrito]) -> Vec<i32> {
+        inscritos.iter().map(|x| x.jogador_id).collect()
+    }
+
+    /// Inscreve um jogador novo para cada nome, na ordem dada.
+    async fn inscritos<const N: usize>(
+        db: &DatabaseConnection,
+        jogo: &jogo::Model,
+        nomes: [&str; N],
+    ) -> [i32; N] {
+        let mut ids = [0; N];
+        for (id, nome) in ids.iter_mut().zip(nomes) {
+            *id = cria_jogador(db, nome).await.id;
+            inscrever(db, jogo.id, *id).await.unwrap();
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn inscrever_duas_vezes_nao_duplica() {
+        let db = banco().await;
+        let jogo = cria_jogo(&db, 10).await;
+        let ana = cria_jogador(&db, "ana").await;
+
+        let (a, b) = tokio::join!(
+            inscrever(&db, jogo.id, ana.id),
+            inscrever(&db, jogo.id, ana.id)
+        );
+        a.unwrap();
+        b.unwrap();
+        inscrever(&db, jogo.id, ana.id).await.unwrap();
+
+        assert_eq!(lista_do_jogo(&db, &jogo).await.unwrap().confirmados.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn lista_separa_confirmados_da_espera() {
+        let db = banco().await;
+        let jogo = cria_jogo(&db, 2).await;
+        let [ana, bia, caio] = inscritos(&db, &jogo, ["ana", "bia", "caio"]).await;
+
+        let lista = lista_do_jogo(&db, &jogo).await.unwrap();
+
+        assert_eq!(ids(&lista.confirmados), vec![ana, bia]);
+        assert_eq!(ids(&lista.espera), vec![caio]);
+        assert_eq!(lista.posicao(bia), Some(Posicao::Confirmado(2)));
+        assert_eq!(lista.posicao(caio), Some(Posicao::Espera(1)));
+    }
+
+    #[tokio::test]
+    async fn espera_segue_a_ordem_de_inscricao() {
+        let db = banco().await;
+        let jogo = cria_jogo(&db, 1).await;
+        let agora = Utc::now();
+        let mut ids_por_ordem = vec![];
+        // Gravados fora da ordem de inscrição, para a ordem não vir do id
+        for (nome, minutos) in [("ana", 2), ("bia", 0), ("caio", 1)] {
+            let jogador = cria_jogador(&db, nome).await;
+            Inscricao::insert(inscricao::ActiveModel {
+                jogo_id: ActiveValue::Set(jogo.id),
+                jogador_id: ActiveValue::Set(jogador.id),
+                criado_em: ActiveValue::Set(agora + Duration::minutes(minutos)),
+                ..Default::default()
+            })
+            .exec(&db)
+            .await
+            .unwrap();
+            ids_por_ordem.push((minutos, jogador.id));
+        }
+        ids_por_ordem.sort();
+        let ordem = ids_por_ordem.into_iter().map(|(_, id)| id).collect::<Vec<_>>();
+
+        let lista = lista_do_jogo(&db, &jogo).await.unwrap();
+
+        assert_eq!(ids(&lista.confirmados), ordem[..1]);
+        assert_eq!(ids(&lista.espera), ordem[1..]);
+    }
+
+    #[tokio::test]
+    async fn sair_promove_o_primeiro_da_espera() {
+        let db = banco().await;
+        let jogo = cria_jogo(&db, 1).await;
+        let [ana, bia, caio] = inscritos(&db, &jogo, ["ana", "bia", "caio"]).await;
+
+        let promovido = sair(&db, &jogo, ana).await.unwrap();
+
+        assert_eq!(promovido.map(|p| p.jogador_id), Some(bia));
+        let lista = lista_do_jogo(&db, &jogo).await.unwrap();
+        assert_eq!(ids(&lista.confirmados), vec![bia]);
+        assert_eq!(ids(&lista.espera), vec![caio]);
+    }
+
+    #[tokio::test]
+    async fn sair_sem_espera_nao_promove() {
+        let db = banco().await;
+        let jogo = cria_jogo(&db, 2).await;
+        let [ana, bia] = inscritos(&db, &jogo, ["ana", "bia"]).await;
+
+        assert!(sair(&db, &jogo, ana).await.unwrap().is_none());
+        assert_eq!(ids(&lista_do_jogo(&db, &jogo).await.unwrap().confirmados), vec![bia]);
+        // Quem não está inscrito também não promove ninguém
+        assert!(sair(&db, &jogo, ana).await.unwrap().is_none());
+    }
+}
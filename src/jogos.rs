@@ -0,0 +1,154 @@
+use chrono::{DateTime, Days, Utc};
+use lazy_static::lazy_static;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+
+use crate::entities::{prelude::*, *};
+use crate::error::Result;
+use crate::timings::{self, ref_point_from_id, ref_point_id};
+
+/// Capacidade de um jogo novo, o antigo limite fixo da lista.
+pub const CAPACIDADE_PADRAO: i32 = 24;
+
+/// Quantos candidatos vêm em cada cédula, quando não é configurado.
+pub const TAMANHO_CEDULA_PADRAO: i32 = 5;
+/// Com menos de dois candidatos não há o que ordenar.
+pub const TAMANHO_CEDULA_MINIMO: i32 = 2;
+pub const TAMANHO_CEDULA_MAXIMO: i32 = 10;
+
+lazy_static! {
+    /// Tamanho das cédulas da votação semanal.
+    pub static ref TAMANHO_CEDULA_SEMANAL: i32 =
+        tamanho_cedula_semanal_from_env().expect("Invalid TAMANHO_CEDULA_SEMANAL");
+}
+
+/// Lê `TAMANHO_CEDULA_SEMANAL`, por padrão [`TAMANHO_CEDULA_PADRAO`].
+fn tamanho_cedula_semanal_from_env() -> anyhow::Result<i32> {
+    let Ok(valor) = std::env::var("TAMANHO_CEDULA_SEMANAL") else {
+        return Ok(TAMANHO_CEDULA_PADRAO);
+    };
+    validar_tamanho_cedula(valor.trim().parse()?)
+}
+
+pub fn validar_tamanho_cedula(tamanho: i32) -> anyhow::Result<i32> {
+    if !(TAMANHO_CEDULA_MINIMO..=TAMANHO_CEDULA_MAXIMO).contains(&tamanho) {
+        anyhow::bail!(
+            "ballot size must be between {} and {}, got {}",
+            TAMANHO_CEDULA_MINIMO,
+            TAMANHO_CEDULA_MAXIMO,
+            tamanho
+        );
+    }
+    Ok(tamanho)
+}
+
+/// Horário de início do jogo, que também é quando abre a votação dele.
+pub fn kickoff(jogo: &jogo::Model) -> DateTime<Utc> {
+    timings::from_local(jogo.data.and_time(jogo.hora))
+}
+
+/// Semana (`week_id`) a que o jogo pertence.
+pub fn week_id(jogo: &jogo::Model) -> i32 {
+    ref_point_id(kickoff(jogo))
+}
+
+/// Início e fim do dia local do jogo, usados para achar quem estava na lista dele.
+pub fn dia_do_jogo(jogo: &jogo::Model) -> (DateTime<Utc>, DateTime<Utc>) {
+    let inicio = jogo.data.and_hms_opt(0, 0, 0).unwrap();
+    let fim = inicio.checked_add_days(Days::new(1)).unwrap();
+    (timings::from_local(inicio), timings::from_local(fim))
+}
+
+/// Jogos não cancelados cuja votação começa dentro da semana `week_id`, em
+/// ordem de horário.
+pub async fn jogos_da_semana(db: &DatabaseConnection, week_id: i32) -> Result<Vec<jogo::Model>> {
+    jogos_entre(db, ref_point_from_id(week_id), ref_point_from_id(week_id + 1)).await
+}
+
+/// Jogos não cancelados que começam em `[inicio, fim)`, em ordem de horário.
+pub async fn jogos_entre(
+    db: &DatabaseConnection,
+    inicio: DateTime<Utc>,
+    fim: DateTime<Utc>,
+) -> Result<Vec<jogo::Model>> {
+    let tz = timings::timezone();
+
+    let jogos = Jogo::find()
+        .filter(jogo::Column::Cancelado.eq(false))
+        .filter(jogo::Column::Data.gte(inicio.with_timezone(&tz).date_naive()))
+        .filter(jogo::Column::Data.lte(fim.with_timezone(&tz).date_naive()))
+        .order_by_asc(jogo::Column::Data)
+        .order_by_asc(jogo::Column::Hora)
+        .all(db)
+        .await?;
+
+    Ok(jogos
+        .into_iter()
+        .filter(|j| {
+            let k = kickoff(j);
+            k >= inicio && k < fim
+        })
+        .collect())
+}
+
+/// Jogos em que ainda é possível criar uma cédula em `date`.
+pub async fn jogos_em_votacao(
+    db: &DatabaseConnection,
+    date: DateTime<Utc>,
+) -> Result<Vec<jogo::Model>> {
+    // A votação de um jogo nunca dura mais que algumas semanas, então basta olhar
+    // para os jogos recentes.
+    let tz = timings::timezone();
+    let desde = (date - chrono::Duration::weeks(4)).with_timezone(&tz).date_naive();
+
+    let jogos = Jogo::find()
+        .filter(jogo::Column::Cancelado.eq(false))
+        .filter(jogo::Column::Data.gte(desde))
+        .filter(jogo::Column::Data.lte(date.with_timezone(&tz).date_naive()))
+        .order_by_asc(jogo::Column::Data)
+        .order_by_asc(jogo::Column::Hora)
+        .all(db)
+        .await?;
+
+    Ok(jogos
+        .into_iter()
+        .filter(|j| timings::can_create_ballot_jogo(kickoff(j), date))
+        .collect())
+}
+
+/// Jogos que ainda não começaram, incluindo os cancelados para que apareçam
+/// como tal na página inicial.
+pub async fn proximos_jogos(
+    db: &DatabaseConnection,
+    date: DateTime<Utc>,
+) -> Result<Vec<jogo::Model>> {
+    let hoje = date.with_timezone(&timings::timezone()).date_naive();
+
+    let jogos = Jogo::find()
+        .filter(jogo::Column::Data.gte(hoje))
+        .order_by_asc(jogo::Column::Data)
+        .order_by_asc(jogo::Column::Hora)
+        .all(db)
+        .await?;
+
+    Ok(jogos.into_iter().filter(|j| kickoff(j) > date).collect())
+}
+
+/// Próximo jogo não cancelado, se houver.
+pub async fn proximo_jogo(
+    db: &DatabaseConnection,
+    date: DateTime<Utc>,
+) -> Result<Option<jogo::Model>> {
+    Ok(proximos_jogos(db, date)
+        .await?
+        .into_iter()
+        .find(|j| !j.cancelado))
+}
+
+/// Quando o ranking da semana pode ser publicado: o horário normal da semana,
+/// ou depois, se a votação de algum jogo da semana terminar mais tarde.
+pub fn publish_time_semana(week_id: i32, jogos: &[jogo::Model]) -> DateTime<Utc> {
+    jogos
+        .iter()
+        .map(|j| timings::publish_time_jogo(kickoff(j)))
+        .fold(timings::publish_time(ref_point_from_id(week_id)), |a, b| a.max(b))
+}
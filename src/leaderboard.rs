@@ -0,0 +1,226 @@
+//! Classificação acumulada de várias semanas, a partir das apurações semanais
+//! já gravadas.
+
+use std::collections::BTreeMap;
+
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use serde::Serialize;
+
+use crate::entities::sea_orm_active_enums::ApuracaoState;
+use crate::entities::{apuracao, prelude::*};
+use crate::error::Result;
+use crate::ranking::Ranking;
+
+/// Semanas apuradas que um jogador precisa ter para entrar na classificação.
+pub const MINIMO_PADRAO: usize = 3;
+
+/// Última posição do pódio.
+const PODIO: i32 = 3;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct Colocacao {
+    pub week_id: i32,
+    pub pos: i32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Classificado {
+    pub pos: i32,
+    pub id: i32,
+    pub nome: String,
+    pub semanas: usize,
+    pub posicao_media: f32,
+    /// Média do percentil (`media`) nas semanas jogadas.
+    pub media: f32,
+    pub podios: usize,
+    pub melhor: Colocacao,
+    pub pior: Colocacao,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Leaderboard {
+    pub de: Option<i32>,
+    pub ate: Option<i32>,
+    pub minimo: usize,
+    /// Semanas apuradas no intervalo.
+    pub semanas: Vec<i32>,
+    pub jogadores: Vec<Classificado>,
+    /// Quantos jogadores ficaram de fora por não atingir o mínimo.
+    pub abaixo_do_minimo: usize,
+}
+
+/// Agrega os rankings semanais, em qualquer ordem. Quem jogou menos de
+/// `minimo` semanas fica de fora; os demais são ordenados pela posição média,
+/// desempatando por mais semanas jogadas.
+pub fn agregar(rankings: &[(i32, Ranking)], minimo: usize) -> (Vec<Classificado>, usize) {
+    let mut por_jogador: BTreeMap<i32, (String, Vec<(Colocacao, f32)>)> = BTreeMap::new();
+    for (week_id, ranking) in rankings {
+        for entry in &ranking.entries {
+            let (nome, semanas) = por_jogador
+                .entry(entry.id)
+                .or_insert_with(|| (entry.nome.clone(), vec![]));
+            // O nome mais recente vale
+            if semanas.iter().all(|(c, _)| c.week_id < *week_id) {
+                *nome = entry.nome.clone();
+            }
+            let colocacao = Colocacao {
+                week_id: *week_id,
+                pos: entry.pos,
+            };
+            semanas.push((colocacao, entry.media));
+        }
+    }
+
+    let total = por_jogador.len();
+    let mut jogadores = por_jogador
+        .into_iter()
+        .filter(|(_, (_, semanas))| semanas.len() >= minimo.max(1))
+        .map(|(id, (nome, mut semanas))| {
+            semanas.sort_by_key(|(c, _)| c.week_id);
+            let n = semanas.len() as f32;
+            // Em empates, a primeira semana em que aconteceu
+            let melhor = semanas
+                .iter()
+                .map(|(c, _)| *c)
+                .min_by_key(|c| c.pos)
+                .unwrap();
+            let pior = semanas
+                .iter()
+                .map(|(c, _)| *c)
+                .max_by_key(|c| (c.pos, -c.week_id))
+                .unwrap();
+            Classificado {
+                pos: 0,
+                id,
+                nome,
+                semanas: semanas.len(),
+                posicao_media: semanas.iter().map(|(c, _)| c.pos as f32).sum::<f32>() / n,
+                media: semanas.iter().map(|(_, m)| m).sum::<f32>() / n,
+                podios: semanas.iter().filter(|(c, _)| c.pos <= PODIO).count(),
+                melhor,
+                pior,
+            }
+        })
+        .collect::<Vec<_>>();
+    let abaixo_do_minimo = total - jogadores.len();
+
+    jogadores.sort_by(|a, b| {
+        a.posicao_media
+            .total_cmp(&b.posicao_media)
+            .then(b.semanas.cmp(&a.semanas))
+            .then(a.id.cmp(&b.id))
+    });
+    for (i, jogador) in jogadores.iter_mut().enumerate() {
+        jogador.pos = (i + 1) as i32;
+    }
+    (jogadores, abaixo_do_minimo)
+}
+
+/// Classificação das semanas apuradas entre `de` e `ate`, inclusive; sem
+/// limites, todas as semanas.
+pub async fn leaderboard(
+    db: &DatabaseConnection,
+    de: Option<i32>,
+    ate: Option<i32>,
+    minimo: usize,
+) -> Result<Leaderboard> {
+    let mut query = Apuracao::find().filter(apuracao::Column::State.eq(ApuracaoState::Complete));
+    if let Some(de) = de {
+        query = query.filter(apuracao::Column::WeekId.gte(de));
+    }
+    if let Some(ate) = ate {
+        query = query.filter(apuracao::Column::WeekId.lte(ate));
+    }
+    let rankings = query
+        .order_by_asc(apuracao::Column::WeekId)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|a| Ok((a.week_id, serde_json::from_value::<Ranking>(a.results)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (jogadores, abaixo_do_minimo) = agregar(&rankings, minimo);
+    Ok(Leaderboard {
+        de,
+        ate,
+        minimo,
+        semanas: rankings.iter().map(|(week_id, _)| *week_id).collect(),
+        jogadores,
+        abaixo_do_minimo,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::ranking::RankingEntry;
+
+    fn semana(ids: &[i32]) -> Ranking {
+        Ranking {
+            entries: ids
+                .iter()
+                .enumerate()
+                .map(|(i, id)| RankingEntry {
+                    pos: i as i32 + 1,
+                    nome: format!("Jogador {id}"),
+                    id: *id,
+                    media: 1. - i as f32 / ids.len() as f32,
+                    votos: 5,
+                    desvio_padrao: None,
+                })
+                .collect(),
+            timestamp: Utc::now(),
+            votes: 10,
+            estrategia: "atual".to_string(),
+        }
+    }
+
+    #[test]
+    fn agrega_posicoes_e_podios() {
+        let rankings = [
+            (10, semana(&[1, 2, 3, 4])),
+            (11, semana(&[2, 1, 4, 3])),
+            (12, semana(&[1, 4, 2, 3])),
+        ];
+        let (jogadores, abaixo) = agregar(&rankings, 2);
+
+        assert_eq!(abaixo, 0);
+        assert_eq!(
+            jogadores.iter().map(|j| j.id).collect::<Vec<_>>(),
+            vec![1, 2, 4, 3]
+        );
+        let primeiro = &jogadores[0];
+        assert_eq!(primeiro.semanas, 3);
+        assert_eq!(primeiro.podios, 3);
+        assert_eq!(
+            primeiro.melhor,
+            Colocacao {
+                week_id: 10,
+                pos: 1
+            }
+        );
+        assert_eq!(
+            primeiro.pior,
+            Colocacao {
+                week_id: 11,
+                pos: 2
+            }
+        );
+        assert!((primeiro.posicao_media - 4. / 3.).abs() < 1e-6);
+        assert_eq!(jogadores[3].podios, 1);
+    }
+
+    #[test]
+    fn minimo_de_semanas_exclui_quem_jogou_pouco() {
+        let rankings = [(1, semana(&[5, 1, 2])), (2, semana(&[1, 2]))];
+        let (jogadores, abaixo) = agregar(&rankings, 2);
+
+        assert_eq!(abaixo, 1);
+        assert_eq!(
+            jogadores.iter().map(|j| j.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+}
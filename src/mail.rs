@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub para: String,
+    pub assunto: String,
+    pub corpo: String,
+}
+
+/// Forma de entregar os emails enviados pelo site.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> anyhow::Result<()>;
+}
+
+/// Só escreve o email no log, útil para desenvolvimento.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> anyhow::Result<()> {
+        tracing::info!(
+            to = %email.para,
+            subject = %email.assunto,
+            "Email:\n{}",
+            email.corpo
+        );
+        Ok(())
+    }
+}
+
+/// Adiciona cada email no fim de um arquivo, como uma caixa de saída.
+pub struct FileMailer {
+    path: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> anyhow::Result<()> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        let content = format!(
+            "To: {}\nSubject: {}\nDate: {}\n\n{}\n\n---\n",
+            email.para,
+            email.assunto,
+            chrono::Utc::now().to_rfc2822(),
+            email.corpo
+        );
+        file.write_all(content.as_bytes()).await?;
+        tracing::info!("Email to {} written to {}", email.para, self.path.display());
+        Ok(())
+    }
+}
+
+/// Usa `MAIL_OUTBOX` como arquivo de saída se estiver definido, senão só loga.
+pub fn mailer_from_env() -> Box<dyn Mailer> {
+    match std::env::var("MAIL_OUTBOX") {
+        Ok(path) => Box::new(FileMailer::new(path)),
+        Err(_) => Box::new(LogMailer),
+    }
+}
@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use std::time::Duration;
 
 use actix_web::{cookie::Key, web::Data, App, HttpServer};
@@ -8,7 +9,6 @@ use env_logger::Env;
 use migration::{Migrator, MigratorTrait};
 use sea_orm::{Database, DatabaseConnection};
 use secrecy::{ExposeSecret, SecretBox, SecretString};
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 
 use actix_identity::IdentityMiddleware;
 use actix_session::{storage::CookieSessionStore, SessionMiddleware};
@@ -18,18 +18,40 @@ use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
 use tracing::subscriber::set_global_default;
 
+mod alfio;
 mod db;
+mod eligibility;
 mod entities;
 mod error;
+#[cfg(test)]
+mod fixtures;
+mod guards;
+mod inscricoes;
+mod jogos;
+mod leaderboard;
 mod list;
+mod mail;
+mod perfil;
 mod ranking;
+mod rating;
+mod roster;
+mod scoring;
 mod services;
+mod sorteio;
 mod templates;
 mod timings;
+mod tokens;
 
 pub struct AppState {
-    alfio_db: Pool<Postgres>,
+    roster: Arc<dyn roster::RosterSource>,
     db: DatabaseConnection,
+    mailer: Arc<dyn mail::Mailer>,
+    /// Endereço público do site, usado nos links enviados por email.
+    public_url: String,
+    /// Como sortear os candidatos de cada cédula.
+    sorteio: sorteio::Sorteio,
+    /// Como transformar as cédulas em ranking na apuração.
+    scoring: Arc<dyn scoring::ScoringStrategy>,
 }
 
 #[actix_web::main]
@@ -46,6 +68,11 @@ async fn main() -> std::io::Result<()> {
 
     set_global_default(subscriber).expect("Error setting global default");
 
+    // Falha logo na inicialização se a configuração for inválida
+    lazy_static::initialize(&timings::SCHEDULE);
+    lazy_static::initialize(&eligibility::REGRAS);
+    lazy_static::initialize(&jogos::TAMANHO_CEDULA_SEMANAL);
+
     let cookie_key_master_str = SecretBox::from(
         std::env::var("COOKIE_KEY_MASTER").expect("COOKIE_KEY_MASTER must be set")
     );
@@ -60,12 +87,6 @@ async fn main() -> std::io::Result<()> {
 
     // Database
 
-    let database_url = SecretBox::from(std::env::var("DATABASE_URL").expect("DATABASE_URL must be set"));
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url.expose_secret())
-        .await
-        .expect("Error building a connection pool");
     let local_db_url = SecretBox::from(std::env::var("LOCAL_DATABASE_URL").expect("LOCAL_DATABASE_URL must be set"));
     let db = Database::connect(local_db_url.expose_secret())
         .instrument(tracing::info_span!("local db connection"))
@@ -78,6 +99,22 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Error running migrations");
 
+    // `futeinf rebuild-ratings` recalcula o rating de temporada do zero e sai
+    if std::env::args().nth(1).as_deref() == Some("rebuild-ratings") {
+        let cedulas = rating::rebuild_ratings(&db)
+            .await
+            .expect("Error rebuilding ratings");
+        tracing::info!("Rebuilt season ratings from {} ballots", cedulas);
+        return Ok(());
+    }
+
+    let roster: Arc<dyn roster::RosterSource> = Arc::from(roster::roster_from_env(&db).await);
+    let mailer: Arc<dyn mail::Mailer> = Arc::from(mail::mailer_from_env());
+    let public_url =
+        std::env::var("PUBLIC_URL").unwrap_or_else(|_| "http://localhost:8080".to_string());
+    let sorteio = sorteio::Sorteio::from_env();
+    let scoring: Arc<dyn scoring::ScoringStrategy> = Arc::from(scoring::scoring_from_env());
+
     HttpServer::new(move || {
         let identity = IdentityMiddleware::builder()
             .visit_deadline(Some(Duration::from_secs(60 * 60 * 24 * 7)))
@@ -98,24 +135,66 @@ async fn main() -> std::io::Result<()> {
             // order of registration when it receives an incoming request.
             .wrap( cookie_middle.build())
             .app_data(Data::new(AppState {
-                alfio_db: pool.clone(),
+                roster: roster.clone(),
                 db: db.clone(),
+                mailer: mailer.clone(),
+                public_url: public_url.clone(),
+                sorteio,
+                scoring: scoring.clone(),
             }))
             .wrap(TracingLogger::default())
             .service(services::lista::index)
             .service(services::lista::jogadores)
+            .service(services::lista::lista_jogo)
+            .service(services::lista::inscrever)
+            .service(services::lista::sair)
+            .service(services::voting::voting_choose_jogo)
             .service(services::voting::vote)
             .service(services::voting::vote_submit)
+            .service(services::voting::vote_reopen)
             .service(services::voting::voting_create)
             .service(services::voting::vote_success)
+            .service(services::voting::explain_elegible_players)
             .service(services::voting::get_elegible_players)
             .service(services::ranking::debug_ranking)
+            .service(services::ranking::debug_ranking_week)
+            .service(services::ranking::rescore_week)
             .service(services::ranking::week_ranking)
+            .service(services::ranking::jogo_ranking)
+            .service(services::leaderboard::leaderboard_page)
+            .service(services::leaderboard::leaderboard_json)
+            .service(services::perfil::perfil_jogador)
             .service(services::auth::login_form)
             .service(services::auth::login)
             .service(services::auth::logout)
+            .service(services::auth::register_form)
+            .service(services::auth::register)
+            .service(services::auth::verify_email)
+            .service(services::auth::forgot_password_form)
+            .service(services::auth::forgot_password)
+            .service(services::auth::reset_password_form)
+            .service(services::auth::reset_password)
             .service(services::auth::upload_image)
             .service(services::auth::get_image)
+            .service(services::admin::jogadores)
+            .service(services::admin::jogador_new_form)
+            .service(services::admin::jogador_create)
+            .service(services::admin::jogador_edit_form)
+            .service(services::admin::jogador_update)
+            .service(services::admin::jogador_deactivate)
+            .service(services::admin::jogador_remove_image)
+            .service(services::admin::jogos_page)
+            .service(services::admin::jogo_new_form)
+            .service(services::admin::jogo_create)
+            .service(services::admin::jogo_edit_form)
+            .service(services::admin::jogo_update)
+            .service(services::admin::jogo_cancel)
+            .service(services::admin::lista_extra_page)
+            .service(services::admin::lista_extra_add)
+            .service(services::admin::lista_extra_remove)
+            .service(services::admin::lista_extra_json)
+            .service(services::admin::lista_extra_add_json)
+            .service(services::admin::lista_extra_remove_json)
     })
     .bind(("0.0.0.0", 8080))?
     .run()
@@ -0,0 +1,247 @@
+//! Dados da página de perfil de um jogador: histórico nas apurações
+//! semanais, cédulas e presença.
+
+use chrono::{Duration, Utc};
+use itertools::Itertools;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use serde::Serialize;
+
+use crate::entities::sea_orm_active_enums::{ApuracaoState, BallotState};
+use crate::entities::{prelude::*, *};
+use crate::error::Result;
+use crate::ranking::Ranking;
+use crate::scoring::ordem;
+use crate::AppState;
+
+/// Quantas semanas para trás contam na presença do perfil.
+pub const SEMANAS_PRESENCA: i64 = 12;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Historico {
+    pub week_id: i32,
+    pub pos: i32,
+    /// Quantos jogadores entraram no ranking da semana.
+    pub de: usize,
+    pub media: f32,
+}
+
+/// Cédulas já apuradas; as de semanas em aberto não aparecem antes da
+/// publicação do ranking.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Cedulas {
+    /// Em quantas cédulas apareceu como candidato.
+    pub aparicoes: usize,
+    /// Quantas cédulas o colocaram em primeiro.
+    pub primeiro: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Presenca {
+    /// Eventos em que esteve inscrito, mais as entradas na lista extra.
+    pub presencas: usize,
+    pub eventos: usize,
+    pub semanas: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Perfil {
+    pub historico: Vec<Historico>,
+    pub cedulas: Cedulas,
+    pub presenca: Presenca,
+}
+
+/// Conta as aparições do jogador nas cédulas apuradas.
+pub fn contar_cedulas(jogador_id: i32, ballots: &[ballot::Model]) -> Cedulas {
+    let mut cedulas = Cedulas::default();
+    for ballot in ballots {
+        if ballot.state != BallotState::Computed {
+            continue;
+        }
+        let ordem = ordem(ballot);
+        if ordem.ranked.contains(&jogador_id) || ordem.unranked.contains(&jogador_id) {
+            cedulas.aparicoes += 1;
+        }
+        if ordem.ranked.first() == Some(&jogador_id) {
+            cedulas.primeiro += 1;
+        }
+    }
+    cedulas
+}
+
+/// Gráfico de linha em SVG, sem eixos, com valores entre 0 e 1 (o de cima).
+pub fn sparkline(valores: &[f32], largura: u32, altura: u32) -> String {
+    let margem = 3.;
+    let (largura, altura) = (largura as f32, altura as f32);
+    let passo = (largura - 2. * margem) / (valores.len().max(2) - 1) as f32;
+    let pontos = valores
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let x = margem + i as f32 * passo;
+            let y = margem + (1. - v.clamp(0., 1.)) * (altura - 2. * margem);
+            (x, y)
+        })
+        .collect_vec();
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{largura}" height="{altura}" viewBox="0 0 {largura} {altura}" role="img">"#
+    );
+    if pontos.len() > 1 {
+        svg += &format!(
+            r#"<polyline fill="none" stroke="currentColor" stroke-width="2" points="{}"/>"#,
+            pontos
+                .iter()
+                .map(|(x, y)| format!("{x:.1},{y:.1}"))
+                .join(" ")
+        );
+    }
+    if let Some((x, y)) = pontos.last() {
+        svg += &format!(r#"<circle cx="{x:.1}" cy="{y:.1}" r="3" fill="currentColor"/>"#);
+    }
+    svg += "</svg>";
+    svg
+}
+
+pub async fn perfil(state: &AppState, jogador: &jogador::Model) -> Result<Perfil> {
+    let db = &state.db;
+
+    let historico = Apuracao::find()
+        .filter(apuracao::Column::State.eq(ApuracaoState::Complete))
+        .order_by_asc(apuracao::Column::WeekId)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|a| Ok((a.week_id, serde_json::from_value::<Ranking>(a.results)?)))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter_map(|(week_id, ranking)| {
+            let entry = ranking.entries.iter().find(|e| e.id == jogador.id)?;
+            Some(Historico {
+                week_id,
+                pos: entry.pos,
+                de: ranking.entries.len(),
+                media: entry.media,
+            })
+        })
+        .collect();
+
+    let ballots = Ballot::find()
+        .filter(ballot::Column::State.eq(BallotState::Computed))
+        .filter(Expr::cust_with_values(
+            "EXISTS (SELECT 1 FROM json_each(ballot.players) WHERE json_each.value = ?)",
+            [jogador.id],
+        ))
+        .all(db)
+        .await?;
+    let cedulas = contar_cedulas(jogador.id, &ballots);
+
+    let fim = Utc::now();
+    let inicio = fim - Duration::weeks(SEMANAS_PRESENCA);
+    let ingressos = state.roster.ingressos(inicio, fim).await?;
+    let eventos = ingressos
+        .iter()
+        .map(|x| (x.data, x.evento.as_str()))
+        .unique()
+        .count();
+    let em_eventos = ingressos
+        .iter()
+        .filter(|x| x.email.eq_ignore_ascii_case(&jogador.email))
+        .map(|x| (x.data, x.evento.as_str()))
+        .unique()
+        .count();
+    let na_lista_extra = ListaExtra::find()
+        .filter(lista_extra::Column::JogadorId.eq(jogador.id))
+        .filter(lista_extra::Column::Data.gt(inicio.naive_utc()))
+        .filter(lista_extra::Column::Data.lt(fim.naive_utc()))
+        .all(db)
+        .await?
+        .len();
+
+    Ok(Perfil {
+        historico,
+        cedulas,
+        presenca: Presenca {
+            presencas: em_eventos + na_lista_extra,
+            eventos,
+            semanas: SEMANAS_PRESENCA,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::roster::MemoryRoster;
+
+    fn cedula(vote: &[i32], state: BallotState) -> ballot::Model {
+        ballot::Model {
+            state,
+            ..fixtures::cedula(&[1, 2, 3, 4, 5], vote)
+        }
+    }
+
+    #[test]
+    fn conta_aparicoes_e_primeiros_lugares() {
+        let ballots = [
+            cedula(&[1, 2], BallotState::Computed),
+            cedula(&[2, 1], BallotState::Computed),
+            cedula(&[1], BallotState::Computed),
+            // Semana ainda não apurada: não pode vazar antes da publicação
+            cedula(&[1], BallotState::Submitted),
+            cedula(&[1], BallotState::Generated),
+        ];
+
+        assert_eq!(
+            contar_cedulas(1, &ballots),
+            Cedulas {
+                aparicoes: 3,
+                primeiro: 2
+            }
+        );
+        assert_eq!(contar_cedulas(9, &ballots), Cedulas::default());
+    }
+
+    #[tokio::test]
+    async fn perfil_so_busca_cedulas_apuradas_do_jogador() {
+        let state = fixtures::state_com(MemoryRoster::default()).await;
+        let ana = fixtures::cria_jogador(&state.db, "ana").await;
+        let id = ana.id;
+        for cedula in [
+            fixtures::cedula(&[id, 20], &[id]),
+            fixtures::cedula(&[20, id], &[20]),
+            // Outros candidatos
+            fixtures::cedula(&[20, 30], &[20]),
+            ballot::Model {
+                state: BallotState::Submitted,
+                ..fixtures::cedula(&[id, 20], &[id])
+            },
+            // Antigas também contam
+            ballot::Model {
+                date: Utc::now() - Duration::weeks(60),
+                ..fixtures::cedula(&[id, 20], &[id])
+            },
+        ] {
+            fixtures::cria_cedula(&state.db, cedula).await;
+        }
+
+        let perfil = perfil(&state, &ana).await.unwrap();
+
+        assert_eq!((perfil.cedulas.aparicoes, perfil.cedulas.primeiro), (3, 2));
+    }
+
+    #[test]
+    fn sparkline_vai_de_baixo_para_cima() {
+        let svg = sparkline(&[0., 0.5, 1.], 106, 46);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(
+            svg.contains(r#"points="3.0,43.0 53.0,23.0 103.0,3.0""#),
+            "{svg}"
+        );
+        assert!(svg.contains(r#"<circle cx="103.0" cy="3.0""#));
+        // Um ponto só vira um círculo, sem linha
+        assert!(!sparkline(&[0.5], 106, 46).contains("polyline"));
+    }
+}
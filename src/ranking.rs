@@ -6,17 +6,21 @@ use itertools::Itertools;
 use tracing::info;
 use rand::Rng;
 use sea_orm::{
-    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter,
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel,
+    QueryFilter, Select,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::entities::prelude::{Apuracao, Ballot};
+use crate::entities::prelude::{Apuracao, ApuracaoJogo, Ballot};
+use crate::entities::sea_orm_active_enums::{ApuracaoState, BallotState};
 use crate::error::Result;
-use crate::timings::ref_point_from_id;
+use crate::jogos;
+use crate::rating;
+use crate::scoring::{Atual, ScoringStrategy, ESTRATEGIA_PADRAO};
 use crate::{
     entities::{
         apuracao::{self},
-        ballot,
+        apuracao_jogo, ballot, jogador,
         prelude::Jogador,
     },
     AppState,
@@ -37,25 +41,42 @@ pub struct Ranking {
     pub entries: Vec<RankingEntry>,
     pub timestamp: DateTime<Utc>,
     pub votes: i32,
+    /// Estratégia que gerou o ranking; apurações antigas não gravavam.
+    #[serde(default = "estrategia_padrao")]
+    pub estrategia: String,
 }
 
-const APURACAO_COMPLETE: &str = "complete";
-const APURACAO_STARTED: &str = "started";
+fn estrategia_padrao() -> String {
+    ESTRATEGIA_PADRAO.to_string()
+}
+
+/// Cédulas ainda abertas, que a apuração fecha e conta.
+const ESTADOS_ABERTOS: [BallotState; 2] = [BallotState::Generated, BallotState::Submitted];
+
+/// Cédulas de uma semana que a apuração conta: as abertas e as já fechadas.
+/// A prévia e o rescore usam o mesmo conjunto, para baterem com o resultado.
+fn cedulas_apuraveis(week_id: i32) -> Select<Ballot> {
+    Ballot::find()
+        .filter(
+            ballot::Column::State.is_in(ESTADOS_ABERTOS.into_iter().chain([BallotState::Computed])),
+        )
+        .filter(ballot::Column::FuteId.eq(week_id))
+}
 
 pub async fn prepare_apurar(state: Data<AppState>, id: i32) -> Result<()> {
     info!("Preparing apurar for event {}", id);
     // Fecha ballots
     let db = &state.db;
     let ballots = Ballot::find()
-        .filter(ballot::Column::State.eq("open"))
+        .filter(ballot::Column::State.is_in(ESTADOS_ABERTOS))
         .filter(ballot::Column::FuteId.eq(id))
         .all(db)
         .await?;
 
-    info!("Found {} open ballots", ballots.len());
+    info!("Found {} ballots to compute", ballots.len());
     for ballot in ballots {
         let mut am = ballot.into_active_model();
-        am.state = ActiveValue::Set("closed".to_string());
+        am.state = ActiveValue::Set(BallotState::Computed);
         am.save(db).await?;
     }
     info!("Closed all ballots");
@@ -83,7 +104,7 @@ pub async fn get_or_create_apuracao(state: Data<AppState>, id: i32) -> Result<Ra
         .await?;
 
     if let Some(apuracao) = apuracao {
-        if apuracao.state != APURACAO_COMPLETE {
+        if apuracao.state != ApuracaoState::Complete {
             return Err(anyhow::anyhow!("Apuração não está completa").into());
         }
         let ranking = apuracao.results;
@@ -108,8 +129,9 @@ pub async fn apurar_complete(state: Data<AppState>, id: i32) -> Result<Ranking>
             entries: vec![],
             timestamp: Utc::now(),
             votes: 0,
+            estrategia: state.scoring.nome().to_string(),
         })?),
-        state: ActiveValue::Set(APURACAO_STARTED.to_string()),
+        state: ActiveValue::Set(ApuracaoState::Started),
         ..Default::default()
     };
     let mut nova_apuracao = nova_apuracao.save(db).await?;
@@ -117,32 +139,236 @@ pub async fn apurar_complete(state: Data<AppState>, id: i32) -> Result<Ranking>
     // Prepara apuração
     prepare_apurar(state.clone(), id).await?;
 
-    let ranking = calculate_ranking(state, id).await?;
+    let ranking = calculate_ranking(state.clone(), id).await?;
+
+    nova_apuracao.results = ActiveValue::Set(serde_json::to_value(&ranking)?);
+    nova_apuracao.state = ActiveValue::Set(ApuracaoState::Complete);
+    nova_apuracao.save(db).await?;
+
+    atualizar_ratings_apos_apuracao(db).await;
+
+    Ok(ranking)
+}
+
+pub async fn prepare_apurar_jogo(state: Data<AppState>, jogo_id: i32) -> Result<()> {
+    info!("Preparing apurar for jogo {}", jogo_id);
+    let db = &state.db;
+    let ballots = Ballot::find()
+        .filter(ballot::Column::State.is_in(ESTADOS_ABERTOS))
+        .filter(ballot::Column::JogoId.eq(jogo_id))
+        .all(db)
+        .await?;
+
+    info!("Found {} ballots to compute", ballots.len());
+    for ballot in ballots {
+        let mut am = ballot.into_active_model();
+        am.state = ActiveValue::Set(BallotState::Computed);
+        am.save(db).await?;
+    }
+    info!("Closed all ballots");
+
+    Ok(())
+}
+
+pub async fn get_or_create_apuracao_jogo(state: Data<AppState>, jogo_id: i32) -> Result<Ranking> {
+    let db = &state.db;
+    let apuracao = ApuracaoJogo::find()
+        .filter(apuracao_jogo::Column::JogoId.eq(jogo_id))
+        .one(db)
+        .await?;
+
+    if let Some(apuracao) = apuracao {
+        if apuracao.state != ApuracaoState::Complete {
+            return Err(anyhow::anyhow!("Apuração não está completa").into());
+        }
+        let ranking = serde_json::from_value::<Ranking>(apuracao.results)?;
+        Ok(ranking)
+    } else {
+        info!("Creating new apuracao for jogo {}", jogo_id);
+        apurar_jogo_complete(state.clone(), jogo_id).await
+    }
+}
+
+pub async fn apurar_jogo_complete(state: Data<AppState>, jogo_id: i32) -> Result<Ranking> {
+    // Como jogo_id é unique key, se a apuração já existir, retorna erro
+    let db = &state.clone().db;
+
+    let nova_apuracao = apuracao_jogo::ActiveModel {
+        jogo_id: ActiveValue::Set(jogo_id),
+        results: ActiveValue::Set(serde_json::to_value(Ranking {
+            entries: vec![],
+            timestamp: Utc::now(),
+            votes: 0,
+            estrategia: state.scoring.nome().to_string(),
+        })?),
+        state: ActiveValue::Set(ApuracaoState::Started),
+        ..Default::default()
+    };
+    let mut nova_apuracao = nova_apuracao.save(db).await?;
+
+    prepare_apurar_jogo(state.clone(), jogo_id).await?;
+
+    let ranking = calculate_ranking_jogo(state.clone(), jogo_id).await?;
 
     nova_apuracao.results = ActiveValue::Set(serde_json::to_value(&ranking)?);
-    nova_apuracao.state = ActiveValue::Set(APURACAO_COMPLETE.to_string());
+    nova_apuracao.state = ActiveValue::Set(ApuracaoState::Complete);
     nova_apuracao.save(db).await?;
 
+    atualizar_ratings_apos_apuracao(db).await;
+
     Ok(ranking)
 }
 
+/// O rating de temporada não deve impedir a apuração: se falhar, as cédulas
+/// ficam pendentes e entram na próxima atualização.
+async fn atualizar_ratings_apos_apuracao(db: &DatabaseConnection) {
+    if let Err(e) = rating::atualizar_ratings(db).await {
+        tracing::error!("Failed to update season ratings: {:?}", e);
+    }
+}
+
 #[tracing::instrument(name = "Calculate Ranking", skip(state, id), fields(id = %id))]
 pub async fn calculate_ranking(state: Data<AppState>, id: i32) -> Result<Ranking> {
     let db = &state.clone().db;
-    // Get the start of the week, ie. the last monday
-    let start_of_week = ref_point_from_id(id);
 
     // COMPUTE RANKING
     // Get all votes this week
     let votes = Ballot::find()
-        .filter(ballot::Column::State.contains("closed"))
+        .filter(ballot::Column::State.eq(BallotState::Computed))
         .filter(ballot::Column::FuteId.eq(id))
         .all(db)
         .await?;
 
+    let all_players = Jogador::find().all(db).await?;
+
+    let mut rankings = vec![];
+    for (jogo_id, votes) in agrupar_por_jogo(votes) {
+        match jogo_id {
+            None => rankings.push(state.scoring.score(&votes, &all_players)),
+            Some(jogo_id) => {
+                rankings.push(get_or_create_apuracao_jogo(state.clone(), jogo_id).await?)
+            }
+        }
+    }
+
+    Ok(aggregate_rankings(rankings))
+}
+
+/// O ranking da semana é a agregação dos rankings de cada jogo. Cédulas sem
+/// jogo (votação semanal antiga) formam um grupo próprio, que vem primeiro.
+fn agrupar_por_jogo(votes: Vec<ballot::Model>) -> Vec<(Option<i32>, Vec<ballot::Model>)> {
+    votes
+        .into_iter()
+        .into_group_map_by(|b| b.jogo_id)
+        .into_iter()
+        .sorted_by_key(|(jogo_id, _)| *jogo_id)
+        .collect()
+}
+
+/// Ranking de um grupo de cédulas da prévia, com os dados intermediários.
+#[derive(Debug, Serialize, Clone)]
+pub struct PreviaGrupo {
+    pub jogo_id: Option<i32>,
+    pub ranking: Ranking,
+    /// Só a estratégia atual tem notas por cédula para mostrar.
+    pub detalhes: Option<Detalhes>,
+}
+
+/// Prévia do ranking da semana com o mesmo cálculo da apuração, mas sem criar
+/// apurações nem fechar cédulas. Conta também as cédulas abertas, como a
+/// apuração faria.
+pub async fn previa_semana(
+    db: &DatabaseConnection,
+    week_id: i32,
+    estrategia: &dyn ScoringStrategy,
+) -> Result<(Ranking, Vec<PreviaGrupo>)> {
+    let votes = cedulas_apuraveis(week_id).all(db).await?;
+    let all_players = Jogador::find().all(db).await?;
+
+    let grupos = agrupar_por_jogo(votes)
+        .into_iter()
+        .map(|(jogo_id, votes)| {
+            let detalhes = (estrategia.nome() == Atual.nome())
+                .then(|| score_ballots_detalhado(&votes, &all_players).1);
+            PreviaGrupo {
+                jogo_id,
+                ranking: estrategia.score(&votes, &all_players),
+                detalhes,
+            }
+        })
+        .collect::<Vec<_>>();
+    let ranking = aggregate_rankings(grupos.iter().map(|g| g.ranking.clone()).collect());
+
+    Ok((ranking, grupos))
+}
+
+/// Recalcula uma semana com outra estratégia, para comparar com a apuração.
+/// Não grava nada.
+pub async fn pontuar_semana(
+    db: &DatabaseConnection,
+    week_id: i32,
+    estrategia: &dyn ScoringStrategy,
+) -> Result<Ranking> {
+    let votes = cedulas_apuraveis(week_id).all(db).await?;
+    let all_players = Jogador::find().all(db).await?;
+
+    let rankings = agrupar_por_jogo(votes)
+        .into_iter()
+        .map(|(_, votes)| estrategia.score(&votes, &all_players))
+        .collect();
+    Ok(aggregate_rankings(rankings))
+}
+
+#[tracing::instrument(name = "Calculate Game Ranking", skip(state, jogo_id), fields(jogo_id = %jogo_id))]
+pub async fn calculate_ranking_jogo(state: Data<AppState>, jogo_id: i32) -> Result<Ranking> {
+    let db = &state.db;
+    let votes = Ballot::find()
+        .filter(ballot::Column::State.eq(BallotState::Computed))
+        .filter(ballot::Column::JogoId.eq(jogo_id))
+        .all(db)
+        .await?;
+    let all_players = Jogador::find().all(db).await?;
+
+    Ok(state.scoring.score(&votes, &all_players))
+}
+
+/// Calcula o ranking de um conjunto de cédulas já fechadas.
+pub fn score_ballots(votes: &[ballot::Model], all_players: &[jogador::Model]) -> Ranking {
+    score_ballots_detalhado(votes, all_players).0
+}
+
+/// Peso de cada cédula de um votante, que cai quando ele vota muitas vezes.
+#[derive(Debug, Serialize, Clone)]
+pub struct PoderDeVoto {
+    pub voter: String,
+    pub cedulas: usize,
+    pub peso: f32,
+}
+
+/// Nota que um jogador recebeu em uma cédula.
+#[derive(Debug, Serialize, Clone)]
+pub struct Nota {
+    pub ballot_id: i32,
+    pub voter: String,
+    pub nota: f32,
+    pub peso: f32,
+}
+
+/// Dados intermediários da apuração, mostrados na prévia do ranking.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Detalhes {
+    pub poder_de_voto: Vec<PoderDeVoto>,
+    pub notas: HashMap<i32, Vec<Nota>>,
+}
+
+/// Como [`score_ballots`], mas também devolve os dados intermediários.
+pub fn score_ballots_detalhado(
+    votes: &[ballot::Model],
+    all_players: &[jogador::Model],
+) -> (Ranking, Detalhes) {
     // Count how many votes each voter has cast
     let mut votes_per_voter = HashMap::new();
-    for vote in &votes {
+    for vote in votes {
         let voter = vote.voter.clone();
         let count = votes_per_voter.entry(voter).or_insert(0);
         *count += 1;
@@ -167,6 +393,19 @@ pub async fn calculate_ranking(state: Data<AppState>, id: i32) -> Result<Ranking
 
     info!("Vote power: {:?}", vote_power);
 
+    let mut detalhes = Detalhes {
+        poder_de_voto: votes_per_voter
+            .iter()
+            .map(|(voter, count)| PoderDeVoto {
+                voter: voter.clone(),
+                cedulas: *count,
+                peso: vote_power[voter],
+            })
+            .sorted_by(|a, b| a.voter.cmp(&b.voter))
+            .collect(),
+        notas: HashMap::new(),
+    };
+
     struct Vote {
         vote: f32,
         weight: f32,
@@ -174,11 +413,11 @@ pub async fn calculate_ranking(state: Data<AppState>, id: i32) -> Result<Ranking
 
     // Compute the ranking
     let mut votes_per_player: HashMap<i32, Vec<(f32, f32)>> = HashMap::new();
-    for vote in &votes {
+    for vote in votes {
         // Two categories of votes: "ranked" and "unranked"
-        // Ranked votes are counted as beat/4 points
+        // Ranked votes are counted as beat/(tamanho - 1) points
         // Where beat is the number of players that are ranked lower than the player plus the unranked votes
-        // Unranked votes are counted as (number_of_unranked_votes - 1)/(4*2) points
+        // Unranked votes are counted as (number_of_unranked_votes - 1)/((tamanho - 1)*2) points
         let ranked_votes = vote
             .vote
             .as_array()
@@ -198,14 +437,17 @@ pub async fn calculate_ranking(state: Data<AppState>, id: i32) -> Result<Ranking
             .filter(|x| !ranked_votes.contains(x))
             .collect::<Vec<_>>();
         let unranked_votes_count = unranked_votes.len() as f32;
-        const NUMBER_OF_PLAYERS_IN_VOTE: usize = 4;
+        // The last of the ballot's players beats nobody. Ballots always have
+        // at least two players, clamp in case an old row says otherwise
+        let number_of_players_in_vote =
+            (vote.tamanho.max(jogos::TAMANHO_CEDULA_MINIMO) - 1) as usize;
         let weight = vote_power.get(&vote.voter).unwrap();
         let mut ranked_votes = ranked_votes
             .iter()
             .enumerate()
             .map(|(i, player)| {
-                let beat = NUMBER_OF_PLAYERS_IN_VOTE - i; //
-                let vote = (beat as f32) / (NUMBER_OF_PLAYERS_IN_VOTE as f32);
+                let beat = number_of_players_in_vote.saturating_sub(i);
+                let vote = (beat as f32) / (number_of_players_in_vote as f32);
                 (
                     *player,
                     Vote {
@@ -217,7 +459,7 @@ pub async fn calculate_ranking(state: Data<AppState>, id: i32) -> Result<Ranking
             .collect::<Vec<_>>();
 
         let unranked_votes_value =
-            (unranked_votes_count - 1.) / (NUMBER_OF_PLAYERS_IN_VOTE as f32 * 2.);
+            (unranked_votes_count - 1.) / (number_of_players_in_vote as f32 * 2.);
         for player in unranked_votes {
             ranked_votes.push((
                 *player,
@@ -228,9 +470,15 @@ pub async fn calculate_ranking(state: Data<AppState>, id: i32) -> Result<Ranking
             ));
         }
 
-        for (player, vote) in ranked_votes {
+        for (player, v) in ranked_votes {
+            detalhes.notas.entry(player).or_default().push(Nota {
+                ballot_id: vote.id,
+                voter: vote.voter.clone(),
+                nota: v.vote,
+                peso: v.weight,
+            });
             let entry = votes_per_player.entry(player).or_default();
-            entry.push((vote.vote, vote.weight));
+            entry.push((v.vote, v.weight));
         }
     }
 
@@ -269,7 +517,6 @@ pub async fn calculate_ranking(state: Data<AppState>, id: i32) -> Result<Ranking
 
     info!("Std Dev: {:?}", std_dev);
 
-    let all_players = Jogador::find().all(db).await?;
 
     let mut players_mentioned = all_players
         .iter()
@@ -290,7 +537,7 @@ pub async fn calculate_ranking(state: Data<AppState>, id: i32) -> Result<Ranking
         .collect::<Vec<_>>();
     for (i, player) in players_mentioned
         .iter_mut()
-        .sorted_by(|a, b| b.media.partial_cmp(&a.media).unwrap())
+        .sorted_by(|a, b| b.media.total_cmp(&a.media))
         .enumerate()
     {
         player.pos = (i + 1) as i32;
@@ -306,7 +553,199 @@ pub async fn calculate_ranking(state: Data<AppState>, id: i32) -> Result<Ranking
         entries: players_mentioned,
         timestamp: Utc::now(),
         votes: votes.len() as i32,
+        estrategia: ESTRATEGIA_PADRAO.to_string(),
     };
+    (ranking, detalhes)
+}
 
-    Ok(ranking)
+/// Junta os rankings de vários jogos em um só. A média de cada jogador é
+/// ponderada pelo número de votos que ele recebeu em cada jogo, e o desvio
+/// padrão combina a variação dentro de cada jogo com a variação entre jogos.
+pub fn aggregate_rankings(mut rankings: Vec<Ranking>) -> Ranking {
+    if rankings.len() == 1 {
+        return rankings.pop().unwrap();
+    }
+
+    let mut por_jogador: HashMap<i32, Vec<&RankingEntry>> = HashMap::new();
+    for entry in rankings.iter().flat_map(|r| &r.entries) {
+        por_jogador.entry(entry.id).or_default().push(entry);
+    }
+
+    let mut entries = por_jogador
+        .into_values()
+        .map(|entries| {
+            let votos = entries.iter().map(|e| e.votos).sum::<i32>();
+            let peso = votos.max(1) as f32;
+            let media = entries
+                .iter()
+                .map(|e| e.media * e.votos as f32)
+                .sum::<f32>()
+                / peso;
+            let variancia = entries
+                .iter()
+                .map(|e| {
+                    let desvio = e.desvio_padrao.unwrap_or(0.);
+                    e.votos as f32 * (desvio.powi(2) + (e.media - media).powi(2))
+                })
+                .sum::<f32>()
+                / peso;
+            RankingEntry {
+                pos: 0,
+                nome: entries[0].nome.clone(),
+                id: entries[0].id,
+                media,
+                votos,
+                desvio_padrao: variancia.sqrt().is_finite().then_some(variancia.sqrt()),
+            }
+        })
+        .sorted_by(|a, b| b.media.total_cmp(&a.media).then(a.id.cmp(&b.id)))
+        .collect_vec();
+    for (i, entry) in entries.iter_mut().enumerate() {
+        entry.pos = (i + 1) as i32;
+    }
+
+    let estrategia = rankings.iter().map(|r| r.estrategia.as_str()).unique().join("+");
+    Ranking {
+        entries,
+        timestamp: Utc::now(),
+        votes: rankings.iter().map(|r| r.votes).sum(),
+        estrategia: if estrategia.is_empty() {
+            estrategia_padrao()
+        } else {
+            estrategia
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, cedula, jogadores};
+    use crate::roster::MemoryRoster;
+    use crate::scoring::Borda;
+
+    fn media(ranking: &Ranking, id: i32) -> f32 {
+        ranking.entries.iter().find(|e| e.id == id).unwrap().media
+    }
+
+    #[test]
+    fn cedula_de_cinco_pontua_como_antes() {
+        let players = jogadores(5);
+        let ranking = score_ballots(&[cedula(&[1, 2, 3, 4, 5], &[1, 2, 3])], &players);
+
+        assert_eq!(media(&ranking, 1), 1.0);
+        assert_eq!(media(&ranking, 2), 0.75);
+        assert_eq!(media(&ranking, 3), 0.5);
+        assert_eq!(media(&ranking, 4), 0.125);
+    }
+
+    #[test]
+    fn pontuacao_usa_o_tamanho_da_cedula() {
+        let players = jogadores(3);
+        let ranking = score_ballots(&[cedula(&[1, 2, 3], &[1, 2, 3])], &players);
+
+        assert_eq!(media(&ranking, 1), 1.0);
+        assert_eq!(media(&ranking, 2), 0.5);
+        assert_eq!(media(&ranking, 3), 0.0);
+    }
+
+    #[test]
+    fn tamanho_invalido_nao_gera_nota_invalida() {
+        for tamanho in [0, 1] {
+            let votes = [ballot::Model {
+                tamanho,
+                ..cedula(&[1, 2], &[1])
+            }];
+            let ranking = score_ballots(&votes, &jogadores(2));
+
+            assert!(ranking.entries.iter().all(|e| e.media.is_finite()), "{tamanho}");
+        }
+    }
+
+    #[test]
+    fn detalhes_mostram_o_calculo() {
+        let players = jogadores(5);
+        let votes = (0..3)
+            .map(|i| ballot::Model {
+                id: i,
+                ..cedula(&[1, 2, 3, 4, 5], &[1, 2])
+            })
+            .collect::<Vec<_>>();
+
+        let (ranking, detalhes) = score_ballots_detalhado(&votes, &players);
+
+        let poder = &detalhes.poder_de_voto[0];
+        assert_eq!(poder.cedulas, 3);
+        assert!((poder.peso - 2.5 / 3.).abs() < 1e-6);
+        let notas = &detalhes.notas[&1];
+        assert_eq!(notas.iter().map(|n| n.ballot_id).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert!(notas.iter().all(|n| n.nota == 1.0));
+        assert_eq!(media(&ranking, 1), 1.0);
+    }
+
+    #[test]
+    fn media_invalida_nao_derruba_a_agregacao() {
+        let players = jogadores(2);
+        let mut com_nan = score_ballots(&[cedula(&[1, 2], &[1])], &players);
+        com_nan.entries[0].media = f32::NAN;
+        let normal = score_ballots(&[cedula(&[1, 2], &[2])], &players);
+
+        let ranking = aggregate_rankings(vec![com_nan, normal]);
+
+        assert_eq!(ranking.entries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn previa_usa_a_estrategia_configurada() {
+        let db = fixtures::banco().await;
+        let mut ids = vec![];
+        for nome in ["ana", "bia", "caio"] {
+            ids.push(fixtures::cria_jogador(&db, nome).await.id);
+        }
+        fixtures::cria_cedula(&db, cedula(&ids, &[ids[2], ids[1]])).await;
+
+        let (ranking, grupos) = previa_semana(&db, 1, &Borda).await.unwrap();
+        assert_eq!(ranking.estrategia, "borda");
+        assert_eq!(ranking.entries[0].id, ids[2]);
+        assert!(grupos.iter().all(|g| g.detalhes.is_none()));
+
+        let (ranking, grupos) = previa_semana(&db, 1, &Atual).await.unwrap();
+        assert_eq!(ranking.estrategia, "atual");
+        assert!(grupos.iter().all(|g| g.detalhes.is_some()));
+    }
+
+    #[tokio::test]
+    async fn previa_bate_com_a_apuracao() {
+        let state = Data::new(fixtures::state_com(MemoryRoster::default()).await);
+        let mut ids = vec![];
+        for nome in ["ana", "bia", "caio"] {
+            ids.push(fixtures::cria_jogador(&state.db, nome).await.id);
+        }
+        for (estado, vote) in [
+            (BallotState::Computed, vec![ids[0], ids[1]]),
+            (BallotState::Submitted, vec![ids[1]]),
+            // Nunca votada: a apuração fecha e conta do mesmo jeito
+            (BallotState::Generated, vec![]),
+        ] {
+            let cedula = ballot::Model {
+                state: estado,
+                ..cedula(&ids, &vote)
+            };
+            fixtures::cria_cedula(&state.db, cedula).await;
+        }
+
+        let (previa, _) = previa_semana(&state.db, 1, state.scoring.as_ref())
+            .await
+            .unwrap();
+        let apuracao = apurar_complete(state.clone(), 1).await.unwrap();
+
+        let resumo = |r: &Ranking| {
+            r.entries
+                .iter()
+                .map(|e| (e.id, e.pos, e.media, e.votos))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(previa.votes, 3);
+        assert_eq!(resumo(&previa), resumo(&apuracao));
+    }
 }
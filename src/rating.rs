@@ -0,0 +1,370 @@
+//! Rating de temporada: cada cédula apurada vira comparações par a par entre
+//! os candidatos, e cada jogador tem uma habilidade estimada (`mu`) com uma
+//! incerteza (`sigma`), no modelo de Weng–Lin com Bradley–Terry (o mesmo do
+//! OpenSkill).
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Datelike, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, TransactionTrait,
+};
+use serde::Serialize;
+use tracing::info;
+
+use crate::entities::sea_orm_active_enums::BallotState;
+use crate::entities::{prelude::*, *};
+use crate::error::Result;
+use crate::scoring::{ordem, Ordem};
+use crate::timings;
+
+pub const MU_INICIAL: f64 = 25.;
+pub const SIGMA_INICIAL: f64 = MU_INICIAL / 3.;
+const BETA: f64 = SIGMA_INICIAL / 2.;
+/// Incerteza somada antes de cada cédula, para o rating acompanhar mudanças.
+const TAU: f64 = MU_INICIAL / 300.;
+/// Fração mínima da variância que sobra depois de uma cédula.
+const KAPPA: f64 = 0.0001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Habilidade {
+    pub mu: f64,
+    pub sigma: f64,
+}
+
+impl Default for Habilidade {
+    fn default() -> Self {
+        Habilidade {
+            mu: MU_INICIAL,
+            sigma: SIGMA_INICIAL,
+        }
+    }
+}
+
+/// Atualiza as habilidades com o resultado de uma cédula. Cada votado vence
+/// quem veio depois dele; os não votados empatam no fim e não se comparam
+/// entre si.
+pub fn atualizar(habilidades: &mut HashMap<i32, Habilidade>, cedula: &Ordem) {
+    // Posição de cada jogador; os não votados dividem a última
+    let posicoes = cedula
+        .ranked
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (*id, i))
+        .chain(cedula.unranked.iter().map(|id| (*id, cedula.ranked.len())))
+        .collect::<Vec<_>>();
+    if posicoes.len() < 2 {
+        return;
+    }
+
+    let antes = posicoes
+        .iter()
+        .map(|(id, _)| {
+            let h = habilidades.get(id).copied().unwrap_or_default();
+            let sigma = (h.sigma.powi(2) + TAU.powi(2)).sqrt();
+            (*id, Habilidade { mu: h.mu, sigma })
+        })
+        .collect::<HashMap<_, _>>();
+
+    for (i, pos_i) in &posicoes {
+        let hi = antes[i];
+        let variancia = hi.sigma.powi(2);
+        let (mut omega, mut delta) = (0., 0.);
+        for (q, pos_q) in &posicoes {
+            if q == i || pos_q == pos_i {
+                continue;
+            }
+            let hq = antes[q];
+            let c = (variancia + hq.sigma.powi(2) + 2. * BETA.powi(2)).sqrt();
+            let p = 1. / (1. + ((hq.mu - hi.mu) / c).exp());
+            let s = if pos_i < pos_q { 1. } else { 0. };
+            let gamma = hi.sigma / c;
+            omega += variancia / c * (s - p);
+            delta += gamma * variancia / c.powi(2) * p * (1. - p);
+        }
+        habilidades.insert(
+            *i,
+            Habilidade {
+                mu: hi.mu + omega,
+                sigma: (variancia * (1. - delta).max(KAPPA)).sqrt(),
+            },
+        );
+    }
+}
+
+/// Temporada de uma cédula: o ano, no fuso do FuteINF, em que foi criada.
+pub fn temporada(ballot: &ballot::Model) -> i32 {
+    ballot.date.with_timezone(&timings::timezone()).year()
+}
+
+/// Consome, em ordem cronológica, as cédulas apuradas que ainda não entraram
+/// no rating. Pode ser chamada quantas vezes for preciso.
+///
+/// Se uma cédula nova é anterior a alguma já processada na sua temporada
+/// (um jogo apurado depois da semana, por exemplo), a temporada inteira é
+/// recalculada, para dar o mesmo resultado que [`rebuild_ratings`].
+pub async fn atualizar_ratings(db: &DatabaseConnection) -> Result<usize> {
+    let processadas = RatingCedula::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|r| r.ballot_id)
+        .collect::<HashSet<_>>();
+    let apuradas = Ballot::find()
+        .filter(ballot::Column::State.eq(BallotState::Computed))
+        .order_by_asc(ballot::Column::Date)
+        .order_by_asc(ballot::Column::Id)
+        .all(db)
+        .await?;
+    let (anteriores, novas): (Vec<_>, Vec<_>) =
+        apuradas.iter().partition(|b| processadas.contains(&b.id));
+    if novas.is_empty() {
+        return Ok(0);
+    }
+
+    let mut ultima: HashMap<i32, (DateTime<Utc>, i32)> = HashMap::new();
+    for cedula in &anteriores {
+        let chave = (cedula.date, cedula.id);
+        let ultima = ultima.entry(temporada(cedula)).or_insert(chave);
+        *ultima = (*ultima).max(chave);
+    }
+    let recalcular = novas
+        .iter()
+        .filter(|b| {
+            ultima
+                .get(&temporada(b))
+                .is_some_and(|u| (b.date, b.id) < *u)
+        })
+        .map(|b| temporada(b))
+        .collect::<HashSet<_>>();
+    for temporada in &recalcular {
+        info!("Out of order ballots, rebuilding season {}", temporada);
+    }
+
+    let existentes = Rating::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|r| ((r.temporada, r.jogador_id), r))
+        .collect::<HashMap<_, _>>();
+    let mut temporadas: HashMap<i32, HashMap<i32, Habilidade>> = HashMap::new();
+    let mut partidas: HashMap<(i32, i32), i32> = HashMap::new();
+    for ((temporada, jogador_id), r) in &existentes {
+        if recalcular.contains(temporada) {
+            continue;
+        }
+        temporadas.entry(*temporada).or_default().insert(
+            *jogador_id,
+            Habilidade {
+                mu: r.mu,
+                sigma: r.sigma,
+            },
+        );
+        partidas.insert((*temporada, *jogador_id), r.partidas);
+    }
+
+    // As temporadas recalculadas voltam a consumir todas as suas cédulas
+    let a_aplicar = apuradas
+        .iter()
+        .filter(|b| !processadas.contains(&b.id) || recalcular.contains(&temporada(b)));
+    let mut alterados = HashSet::new();
+    for cedula in a_aplicar {
+        let temporada = temporada(cedula);
+        let ordem = ordem(cedula);
+        // Cédula sem voto não diz nada sobre ninguém
+        if ordem.ranked.is_empty() {
+            continue;
+        }
+        atualizar(temporadas.entry(temporada).or_default(), &ordem);
+        for id in ordem.ranked.iter().chain(&ordem.unranked) {
+            *partidas.entry((temporada, *id)).or_default() += 1;
+            alterados.insert((temporada, *id));
+        }
+    }
+
+    let agora = Utc::now();
+    let txn = db.begin().await?;
+    if !recalcular.is_empty() {
+        Rating::delete_many()
+            .filter(rating::Column::Temporada.is_in(recalcular.iter().copied()))
+            .exec(&txn)
+            .await?;
+    }
+    for (temporada, jogador_id) in alterados {
+        let h = temporadas[&temporada][&jogador_id];
+        let existente = existentes
+            .get(&(temporada, jogador_id))
+            .filter(|_| !recalcular.contains(&temporada));
+        let mut am = match existente {
+            Some(r) => rating::ActiveModel {
+                id: ActiveValue::Unchanged(r.id),
+                ..Default::default()
+            },
+            None => rating::ActiveModel {
+                jogador_id: ActiveValue::Set(jogador_id),
+                temporada: ActiveValue::Set(temporada),
+                ..Default::default()
+            },
+        };
+        am.mu = ActiveValue::Set(h.mu);
+        am.sigma = ActiveValue::Set(h.sigma);
+        am.partidas = ActiveValue::Set(partidas[&(temporada, jogador_id)]);
+        am.atualizado_em = ActiveValue::Set(agora);
+        am.save(&txn).await?;
+    }
+    for cedula in &novas {
+        rating_cedula::ActiveModel {
+            ballot_id: ActiveValue::Set(cedula.id),
+            processado_em: ActiveValue::Set(agora),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+    }
+    txn.commit().await?;
+
+    info!("Rated {} new ballots", novas.len());
+    Ok(novas.len())
+}
+
+/// Apaga os ratings e recalcula tudo desde a primeira cédula apurada.
+pub async fn rebuild_ratings(db: &DatabaseConnection) -> Result<usize> {
+    let txn = db.begin().await?;
+    RatingCedula::delete_many().exec(&txn).await?;
+    Rating::delete_many().exec(&txn).await?;
+    txn.commit().await?;
+
+    atualizar_ratings(db).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn cedula(ranked: &[i32], unranked: &[i32]) -> Ordem {
+        Ordem {
+            ranked: ranked.to_vec(),
+            unranked: unranked.to_vec(),
+        }
+    }
+
+    #[test]
+    fn vencedor_sobe_e_incerteza_cai() {
+        let mut habilidades = HashMap::new();
+        atualizar(&mut habilidades, &cedula(&[1, 2], &[3, 4]));
+
+        assert!(habilidades[&1].mu > MU_INICIAL);
+        assert!(habilidades[&1].mu > habilidades[&2].mu);
+        assert!(habilidades[&2].mu > habilidades[&3].mu);
+        // Não votados empatam entre si
+        assert_eq!(habilidades[&3], habilidades[&4]);
+        assert!(habilidades.values().all(|h| h.sigma < SIGMA_INICIAL));
+    }
+
+    #[test]
+    fn resultados_repetidos_convergem() {
+        let mut habilidades = HashMap::new();
+        for _ in 0..50 {
+            atualizar(&mut habilidades, &cedula(&[1, 2, 3], &[]));
+        }
+        let mu = [1, 2, 3].map(|id| habilidades[&id].mu);
+        assert!(mu[0] > mu[1] && mu[1] > mu[2]);
+        assert!(habilidades.values().all(|h| h.sigma < SIGMA_INICIAL * 0.6));
+    }
+
+    async fn ratings(db: &DatabaseConnection) -> Vec<rating::Model> {
+        Rating::find()
+            .order_by_asc(rating::Column::JogadorId)
+            .all(db)
+            .await
+            .unwrap()
+    }
+
+    fn assert_mesmos_ratings(a: &[rating::Model], b: &[rating::Model]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert_eq!((a.jogador_id, a.partidas), (b.jogador_id, b.partidas));
+            assert!((a.mu - b.mu).abs() < 1e-9);
+            assert!((a.sigma - b.sigma).abs() < 1e-9);
+        }
+    }
+
+    fn vota(vote: &[i32], state: BallotState) -> ballot::Model {
+        ballot::Model {
+            state,
+            ..fixtures::cedula(&[1, 2, 3], vote)
+        }
+    }
+
+    async fn banco_com_jogadores() -> DatabaseConnection {
+        let db = fixtures::banco().await;
+        for nome in ["ana", "bia", "caio"] {
+            fixtures::cria_jogador(&db, nome).await;
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn atualizacao_incremental_e_rebuild_dao_o_mesmo_resultado() {
+        let db = banco_com_jogadores().await;
+        fixtures::cria_cedula(&db, vota(&[1, 2], BallotState::Computed)).await;
+        // Ainda não apurada, fica de fora
+        fixtures::cria_cedula(&db, vota(&[3], BallotState::Submitted)).await;
+
+        assert_eq!(atualizar_ratings(&db).await.unwrap(), 1);
+        assert_eq!(atualizar_ratings(&db).await.unwrap(), 0);
+        fixtures::cria_cedula(&db, vota(&[2, 1], BallotState::Computed)).await;
+        assert_eq!(atualizar_ratings(&db).await.unwrap(), 1);
+        let incremental = ratings(&db).await;
+
+        assert_eq!(rebuild_ratings(&db).await.unwrap(), 2);
+
+        assert_eq!(incremental.len(), 3);
+        assert_mesmos_ratings(&incremental, &ratings(&db).await);
+        assert_eq!(incremental[0].partidas, 2);
+    }
+
+    #[tokio::test]
+    async fn cedula_anterior_apurada_depois_recalcula_a_temporada() {
+        let db = banco_com_jogadores().await;
+        let hoje = Utc::now();
+        let ontem = hoje - chrono::Duration::days(1);
+        // A semana é apurada antes do jogo, que tem cédulas mais antigas
+        fixtures::cria_cedula(
+            &db,
+            ballot::Model {
+                date: hoje,
+                ..vota(&[1, 2, 3], BallotState::Computed)
+            },
+        )
+        .await;
+        atualizar_ratings(&db).await.unwrap();
+        fixtures::cria_cedula(
+            &db,
+            ballot::Model {
+                date: ontem,
+                ..vota(&[3, 2, 1], BallotState::Computed)
+            },
+        )
+        .await;
+        assert_eq!(atualizar_ratings(&db).await.unwrap(), 1);
+        let incremental = ratings(&db).await;
+
+        rebuild_ratings(&db).await.unwrap();
+
+        assert_mesmos_ratings(&incremental, &ratings(&db).await);
+    }
+
+    #[tokio::test]
+    async fn cedula_sem_voto_nao_conta_partida() {
+        let db = banco_com_jogadores().await;
+        fixtures::cria_cedula(&db, vota(&[1], BallotState::Computed)).await;
+        fixtures::cria_cedula(&db, vota(&[], BallotState::Computed)).await;
+
+        assert_eq!(atualizar_ratings(&db).await.unwrap(), 2);
+
+        assert!(ratings(&db).await.iter().all(|r| r.partidas == 1));
+    }
+}
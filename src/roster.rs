@@ -0,0 +1,222 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sea_orm::DatabaseConnection;
+use secrecy::{ExposeSecret, SecretBox};
+use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+
+use crate::error::Result;
+use crate::alfio::AlfioResolver;
+use crate::entities::jogo;
+use crate::{inscricoes, jogos, list};
+
+/// De onde vem a lista de quem se inscreveu para jogar.
+///
+/// A elegibilidade para a votação e o cadastro de contas só conversam com o
+/// roster por aqui, então o site funciona sem o banco do alf.io.
+#[async_trait]
+pub trait RosterSource: Send + Sync {
+    /// Inscrições em eventos que começam entre `inicio` e `fim`.
+    async fn ingressos(&self, inicio: DateTime<Utc>, fim: DateTime<Utc>) -> Result<Vec<Ingresso>>;
+
+    /// Se o email pode criar uma conta no site.
+    async fn email_conhecido(&self, email: &str) -> Result<bool>;
+
+    /// Quem está na lista do jogo.
+    async fn lista_do_jogo(&self, jogo: &jogo::Model) -> Result<Vec<list::Jogador>>;
+}
+
+/// Inscrição de alguém em um evento.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ingresso {
+    pub email: String,
+    pub evento: String,
+    pub data: DateTime<Utc>,
+}
+
+/// Lê os ingressos direto do banco Postgres do alf.io.
+pub struct AlfioRoster {
+    db: Pool<Postgres>,
+    resolver: AlfioResolver,
+}
+
+impl AlfioRoster {
+    pub fn new(db: Pool<Postgres>, resolver: AlfioResolver) -> Self {
+        Self { db, resolver }
+    }
+}
+
+#[derive(Debug)]
+struct TicketCamisa {
+    id: i32,
+    full_name: Option<String>,
+    camisa: Option<String>,
+}
+
+#[async_trait]
+impl RosterSource for AlfioRoster {
+    async fn ingressos(&self, inicio: DateTime<Utc>, fim: DateTime<Utc>) -> Result<Vec<Ingresso>> {
+        let ingressos = sqlx::query_as!(
+            Ingresso,
+            r#"SELECT DISTINCT t.email_address AS "email!", e.short_name AS evento, e.start_ts AS data
+            FROM ticket t JOIN event e ON e.id = t.event_id
+            WHERE e.start_ts BETWEEN $1 AND $2 AND t.email_address IS NOT NULL
+            ORDER BY e.start_ts"#,
+            inicio,
+            fim
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(ingressos)
+    }
+
+    async fn email_conhecido(&self, email: &str) -> Result<bool> {
+        // A conta é ligada ao email usado nos ingressos do alf.io
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM ticket WHERE lower(email_address) = lower($1)) AS "exists!""#,
+            email
+        )
+        .fetch_one(&self.db)
+        .await?;
+        Ok(exists)
+    }
+
+    async fn lista_do_jogo(&self, jogo: &jogo::Model) -> Result<Vec<list::Jogador>> {
+        let (inicio, fim) = jogos::dia_do_jogo(jogo);
+        let event_id = self.resolver.evento(&self.db, jogo.data, inicio, fim).await?;
+        let campo_camisa = self.resolver.campo_camisa(&self.db, event_id).await?;
+
+        // Quem não preencheu a camisa aparece com o nome do ingresso
+        let tickets = sqlx::query_as!(
+            TicketCamisa,
+            "SELECT t.id, t.full_name, f.field_value AS camisa FROM ticket t
+            LEFT JOIN all_ticket_field_values f
+                ON f.ticket_id_fk = t.id AND f.field_configuration_id_fk = $2
+            WHERE t.status = 'ACQUIRED' AND t.event_id = $1
+            ORDER BY t.id",
+            event_id,
+            campo_camisa
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(tickets
+            .into_iter()
+            .map(|t| {
+                let nome = t
+                    .camisa
+                    .filter(|c| !c.trim().is_empty())
+                    .or(t.full_name)
+                    .unwrap_or_default();
+                list::Jogador {
+                    nome: nome.clone(),
+                    apelido: nome,
+                    id: t.id,
+                    imagem: None,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Usa as inscrições feitas no próprio site.
+pub struct LocalRoster {
+    db: DatabaseConnection,
+}
+
+impl LocalRoster {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl RosterSource for LocalRoster {
+    async fn ingressos(&self, inicio: DateTime<Utc>, fim: DateTime<Utc>) -> Result<Vec<Ingresso>> {
+        let mut ingressos = vec![];
+        for jogo in jogos::jogos_entre(&self.db, inicio, fim).await? {
+            let lista = inscricoes::lista_do_jogo(&self.db, &jogo).await?;
+            ingressos.extend(lista.confirmados.into_iter().map(|x| Ingresso {
+                email: x.email,
+                evento: jogo.nome.clone(),
+                data: jogos::kickoff(&jogo),
+            }));
+        }
+        Ok(ingressos)
+    }
+
+    async fn email_conhecido(&self, _email: &str) -> Result<bool> {
+        // Sem cadastro externo qualquer um pode criar uma conta, só quem se
+        // inscrever nos jogos vai poder votar.
+        Ok(true)
+    }
+
+    async fn lista_do_jogo(&self, jogo: &jogo::Model) -> Result<Vec<list::Jogador>> {
+        let lista = inscricoes::lista_do_jogo(&self.db, jogo).await?;
+        Ok(lista
+            .confirmados
+            .into_iter()
+            .map(|x| list::Jogador {
+                nome: x.nome,
+                id: x.jogador_id,
+                apelido: x.apelido,
+                imagem: None,
+            })
+            .collect())
+    }
+}
+
+/// Roster fixo em memória, para testes.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryRoster {
+    pub ingressos: Vec<Ingresso>,
+    pub lista: Vec<list::Jogador>,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl RosterSource for MemoryRoster {
+    async fn ingressos(&self, inicio: DateTime<Utc>, fim: DateTime<Utc>) -> Result<Vec<Ingresso>> {
+        Ok(self
+            .ingressos
+            .iter()
+            .filter(|x| x.data >= inicio && x.data <= fim)
+            .cloned()
+            .collect())
+    }
+
+    async fn email_conhecido(&self, email: &str) -> Result<bool> {
+        Ok(self
+            .ingressos
+            .iter()
+            .any(|x| x.email.eq_ignore_ascii_case(email)))
+    }
+
+    async fn lista_do_jogo(&self, _jogo: &jogo::Model) -> Result<Vec<list::Jogador>> {
+        Ok(self.lista.clone())
+    }
+}
+
+/// Escolhe o roster pela variável `ROSTER_SOURCE`: `alfio` (padrão), que usa
+/// `DATABASE_URL` para conectar no Postgres do alf.io e `ALFIO_CAMPO_CAMISA`
+/// como nome do campo da camisa, ou `local`, que usa só as inscrições do site
+/// e não precisa do alf.io.
+pub async fn roster_from_env(db: &DatabaseConnection) -> Box<dyn RosterSource> {
+    let source = std::env::var("ROSTER_SOURCE").unwrap_or_else(|_| "alfio".to_string());
+    match source.as_str() {
+        "alfio" => {
+            let database_url = SecretBox::from(
+                std::env::var("DATABASE_URL")
+                    .expect("DATABASE_URL must be set when ROSTER_SOURCE=alfio"),
+            );
+            let pool = PgPoolOptions::new()
+                .max_connections(5)
+                .connect(database_url.expose_secret())
+                .await
+                .expect("Error building a connection pool");
+            Box::new(AlfioRoster::new(pool, AlfioResolver::from_env()))
+        }
+        "local" => Box::new(LocalRoster::new(db.clone())),
+        other => panic!("Unknown ROSTER_SOURCE {other:?}, use \"alfio\" or \"local\""),
+    }
+}
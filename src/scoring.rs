@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use itertools::Itertools;
+
+use crate::entities::{ballot, jogador};
+use crate::ranking::{score_ballots, Ranking, RankingEntry};
+
+/// Estratégia usada quando nenhuma é configurada, e a que gerou as apurações
+/// antigas.
+pub const ESTRATEGIA_PADRAO: &str = "atual";
+
+/// Nomes aceitos por [`estrategia`].
+pub const ESTRATEGIAS: [&str; 4] = ["atual", "borda", "bradley_terry", "bayesiana"];
+
+/// Uma forma de transformar as cédulas de um jogo em ranking.
+pub trait ScoringStrategy: Send + Sync {
+    /// Nome gravado junto com o resultado da apuração.
+    fn nome(&self) -> &'static str;
+
+    fn score(&self, votes: &[ballot::Model], all_players: &[jogador::Model]) -> Ranking;
+}
+
+pub fn estrategia(nome: &str) -> Option<Box<dyn ScoringStrategy>> {
+    match nome {
+        "atual" => Some(Box::new(Atual)),
+        "borda" => Some(Box::new(Borda)),
+        "bradley_terry" => Some(Box::new(BradleyTerry::default())),
+        "bayesiana" => Some(Box::new(Bayesiana::default())),
+        _ => None,
+    }
+}
+
+/// Lê `SCORING_STRATEGY`, por padrão a fórmula atual.
+pub fn scoring_from_env() -> Box<dyn ScoringStrategy> {
+    let nome = std::env::var("SCORING_STRATEGY").unwrap_or_else(|_| ESTRATEGIA_PADRAO.to_string());
+    estrategia(&nome).unwrap_or_else(|| {
+        panic!("Unknown SCORING_STRATEGY {nome:?}, use one of {ESTRATEGIAS:?}")
+    })
+}
+
+/// Uma cédula como ordem: os votados em sequência e os não votados empatados
+/// no fim.
+pub(crate) struct Ordem {
+    pub ranked: Vec<i32>,
+    pub unranked: Vec<i32>,
+}
+
+pub(crate) fn ordem(vote: &ballot::Model) -> Ordem {
+    let ids = |json: &serde_json::Value| -> Vec<i32> {
+        serde_json::from_value(json.clone()).unwrap_or_default()
+    };
+    let ranked = ids(&vote.vote);
+    let unranked = ids(&vote.players)
+        .into_iter()
+        .filter(|x| !ranked.contains(x))
+        .collect();
+    Ordem { ranked, unranked }
+}
+
+/// Numera as entradas pela média, da maior para a menor.
+fn posicionar(entries: Vec<RankingEntry>) -> Vec<RankingEntry> {
+    let mut entries = entries
+        .into_iter()
+        .sorted_by(|a, b| b.media.total_cmp(&a.media).then(a.id.cmp(&b.id)))
+        .collect_vec();
+    for (i, entry) in entries.iter_mut().enumerate() {
+        entry.pos = (i + 1) as i32;
+    }
+    entries
+}
+
+fn ranking(estrategia: &dyn ScoringStrategy, entries: Vec<RankingEntry>, votes: usize) -> Ranking {
+    Ranking {
+        entries: posicionar(entries),
+        timestamp: Utc::now(),
+        votes: votes as i32,
+        estrategia: estrategia.nome().to_string(),
+    }
+}
+
+fn nome_de(all_players: &[jogador::Model], id: i32) -> Option<String> {
+    all_players.iter().find(|p| p.id == id).map(|p| p.nome.clone())
+}
+
+/// A fórmula original: `beat/(tamanho - 1)` para os votados, metade do resto
+/// para os não votados, com peso menor para quem vota muitas vezes.
+pub struct Atual;
+
+impl ScoringStrategy for Atual {
+    fn nome(&self) -> &'static str {
+        "atual"
+    }
+
+    fn score(&self, votes: &[ballot::Model], all_players: &[jogador::Model]) -> Ranking {
+        score_ballots(votes, all_players)
+    }
+}
+
+/// Contagem de Borda normalizada: em uma cédula de `n` jogadores a posição
+/// `i` vale `(n - 1 - i)/(n - 1)`, e os não votados dividem as posições que
+/// sobraram.
+pub struct Borda;
+
+impl ScoringStrategy for Borda {
+    fn nome(&self) -> &'static str {
+        "borda"
+    }
+
+    fn score(&self, votes: &[ballot::Model], all_players: &[jogador::Model]) -> Ranking {
+        let mut notas: HashMap<i32, Vec<f32>> = HashMap::new();
+        for vote in votes {
+            let Ordem { ranked, unranked } = ordem(vote);
+            let n = ranked.len() + unranked.len();
+            if n < 2 {
+                continue;
+            }
+            let valor = |pos: usize| (n - 1 - pos) as f32 / (n - 1) as f32;
+            for (i, player) in ranked.iter().enumerate() {
+                notas.entry(*player).or_default().push(valor(i));
+            }
+            let empate = (ranked.len()..n).map(valor).sum::<f32>() / unranked.len().max(1) as f32;
+            for player in &unranked {
+                notas.entry(*player).or_default().push(empate);
+            }
+        }
+
+        let entries = notas
+            .into_iter()
+            .filter_map(|(id, notas)| {
+                let n = notas.len() as f32;
+                let media = notas.iter().sum::<f32>() / n;
+                let variancia = notas.iter().map(|x| (x - media).powi(2)).sum::<f32>() / (n - 1.);
+                Some(RankingEntry {
+                    pos: 0,
+                    nome: nome_de(all_players, id)?,
+                    id,
+                    media,
+                    votos: notas.len() as i32,
+                    desvio_padrao: variancia.sqrt().is_finite().then_some(variancia.sqrt()),
+                })
+            })
+            .collect();
+        ranking(self, entries, votes.len())
+    }
+}
+
+/// Modelo de Bradley–Terry ajustado nas comparações par a par de cada cédula:
+/// cada votado vence quem veio depois dele, empates entre não votados não
+/// contam. A média é a chance de vencer um jogador de força média.
+pub struct BradleyTerry {
+    pub iteracoes: usize,
+    /// Vitórias e derrotas fictícias contra um jogador médio, para que quem
+    /// só venceu ou só perdeu não vá para o infinito.
+    pub prior: f64,
+}
+
+impl Default for BradleyTerry {
+    fn default() -> Self {
+        BradleyTerry {
+            iteracoes: 200,
+            prior: 0.5,
+        }
+    }
+}
+
+impl ScoringStrategy for BradleyTerry {
+    fn nome(&self) -> &'static str {
+        "bradley_terry"
+    }
+
+    fn score(&self, votes: &[ballot::Model], all_players: &[jogador::Model]) -> Ranking {
+        let mut vitorias: HashMap<i32, f64> = HashMap::new();
+        let mut jogos: HashMap<(i32, i32), f64> = HashMap::new();
+        let mut aparicoes: HashMap<i32, i32> = HashMap::new();
+        for vote in votes {
+            let Ordem { ranked, unranked } = ordem(vote);
+            for (i, vencedor) in ranked.iter().enumerate() {
+                for perdedor in ranked[i + 1..].iter().chain(&unranked) {
+                    *vitorias.entry(*vencedor).or_default() += 1.;
+                    let par = (*vencedor.min(perdedor), *vencedor.max(perdedor));
+                    *jogos.entry(par).or_default() += 1.;
+                }
+            }
+            for player in ranked.iter().chain(&unranked) {
+                *aparicoes.entry(*player).or_default() += 1;
+            }
+        }
+
+        // Algoritmo MM de Hunter: p_i = W_i / Σ_j n_ij / (p_i + p_j), com o
+        // jogador médio fixo em força 1
+        let ids = aparicoes.keys().copied().sorted().collect_vec();
+        let mut forca: HashMap<i32, f64> = ids.iter().map(|id| (*id, 1.)).collect();
+        for _ in 0..self.iteracoes {
+            let mut nova = HashMap::new();
+            for id in &ids {
+                let p = forca[id];
+                let mut denominador = 2. * self.prior / (p + 1.);
+                for ((a, b), n) in &jogos {
+                    if a == id || b == id {
+                        let outro = if a == id { b } else { a };
+                        denominador += n / (p + forca[outro]);
+                    }
+                }
+                let w = vitorias.get(id).copied().unwrap_or(0.) + self.prior;
+                nova.insert(*id, w / denominador);
+            }
+            // Normaliza pela média geométrica para a escala não derivar
+            let log_medio = nova.values().map(|p: &f64| p.ln()).sum::<f64>() / nova.len() as f64;
+            forca = nova
+                .into_iter()
+                .map(|(id, p)| (id, p / log_medio.exp()))
+                .collect();
+        }
+
+        let entries = ids
+            .into_iter()
+            .filter_map(|id| {
+                let p = forca[&id];
+                Some(RankingEntry {
+                    pos: 0,
+                    nome: nome_de(all_players, id)?,
+                    id,
+                    media: (p / (p + 1.)) as f32,
+                    votos: aparicoes[&id],
+                    desvio_padrao: None,
+                })
+            })
+            .collect();
+        ranking(self, entries, votes.len())
+    }
+}
+
+/// Média bayesiana sobre a fórmula atual: quem tem poucos votos é puxado
+/// para a média geral, como se tivesse `votos_prior` votos nela.
+pub struct Bayesiana {
+    pub votos_prior: f32,
+}
+
+impl Default for Bayesiana {
+    fn default() -> Self {
+        Bayesiana { votos_prior: 3. }
+    }
+}
+
+impl ScoringStrategy for Bayesiana {
+    fn nome(&self) -> &'static str {
+        "bayesiana"
+    }
+
+    fn score(&self, votes: &[ballot::Model], all_players: &[jogador::Model]) -> Ranking {
+        let base = score_ballots(votes, all_players);
+        let total = base.entries.iter().map(|e| e.votos).sum::<i32>().max(1) as f32;
+        let media_geral = base
+            .entries
+            .iter()
+            .map(|e| e.media * e.votos as f32)
+            .sum::<f32>()
+            / total;
+
+        let entries = base
+            .entries
+            .into_iter()
+            .map(|e| {
+                let votos = e.votos as f32;
+                RankingEntry {
+                    media: (votos * e.media + self.votos_prior * media_geral)
+                        / (votos + self.votos_prior),
+                    ..e
+                }
+            })
+            .collect();
+        ranking(self, entries, votes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, jogadores};
+
+    fn cedula(voter: &str, vote: &[i32]) -> ballot::Model {
+        ballot::Model {
+            voter: voter.to_string(),
+            ..fixtures::cedula(&[1, 2, 3, 4, 5], vote)
+        }
+    }
+
+    fn ordem_final(ranking: &Ranking) -> Vec<i32> {
+        ranking.entries.iter().map(|e| e.id).collect()
+    }
+
+    #[test]
+    fn todas_concordam_com_votos_unanimes() {
+        let votes = (0..4)
+            .map(|i| cedula(&format!("v{i}"), &[1, 2, 3, 4, 5]))
+            .collect_vec();
+        for nome in ESTRATEGIAS {
+            let estrategia = estrategia(nome).unwrap();
+            let ranking = estrategia.score(&votes, &jogadores(5));
+            assert_eq!(ranking.estrategia, nome);
+            assert_eq!(ordem_final(&ranking), vec![1, 2, 3, 4, 5], "{nome}");
+        }
+    }
+
+    #[test]
+    fn borda_divide_as_posicoes_dos_nao_votados() {
+        let ranking = Borda.score(&[cedula("v", &[3, 1])], &jogadores(5));
+        let media = |id| ranking.entries.iter().find(|e| e.id == id).unwrap().media;
+
+        assert_eq!(media(3), 1.0);
+        assert_eq!(media(1), 0.75);
+        assert_eq!(media(2), 0.25);
+    }
+
+    #[test]
+    fn bayesiana_puxa_poucos_votos_para_a_media() {
+        let votes = [cedula("a", &[1, 2]), cedula("b", &[1, 2]), cedula("c", &[2, 1])];
+        let atual = Atual.score(&votes, &jogadores(5));
+        let bayesiana = Bayesiana::default().score(&votes, &jogadores(5));
+        let media = |r: &Ranking, id| r.entries.iter().find(|e| e.id == id).unwrap().media;
+
+        assert!(media(&bayesiana, 1) < media(&atual, 1));
+        assert!(media(&bayesiana, 5) > media(&atual, 5));
+    }
+}
@@ -0,0 +1,752 @@
+use crate::entities::{prelude::*, *};
+use crate::error::{create_bad_request, Result};
+use crate::guards::AdminUser;
+use crate::services::auth::hash_password;
+use crate::eligibility::{self, Escopo};
+use crate::templates::TEMPLATES;
+use crate::timings::{self, ref_point_from_id, ref_point_id};
+use crate::{jogos, AppState};
+use actix_web::web::{Data, Path};
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use chrono::prelude::*;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, IntoActiveModel, ModelTrait,
+    QueryFilter, QueryOrder,
+};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Debug)]
+struct JogadorAdmin {
+    id: i32,
+    nome: String,
+    apelido: String,
+    email: String,
+    admin: bool,
+    ativo: bool,
+    tem_imagem: bool,
+}
+
+impl From<jogador::Model> for JogadorAdmin {
+    fn from(j: jogador::Model) -> Self {
+        JogadorAdmin {
+            id: j.id,
+            nome: j.nome,
+            apelido: j.apelido,
+            email: j.email,
+            admin: j.admin,
+            ativo: j.ativo,
+            tem_imagem: j.imagem.is_some(),
+        }
+    }
+}
+
+#[tracing::instrument(name = "Render Admin Jogadores", skip(state, _admin))]
+#[get("/admin/jogadores")]
+pub async fn jogadores(
+    state: Data<AppState>,
+    _admin: AdminUser,
+) -> Result<impl Responder> {
+    let jogadores = Jogador::find()
+        .order_by_asc(jogador::Column::Nome)
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .map(JogadorAdmin::from)
+        .collect::<Vec<_>>();
+
+    let mut context = tera::Context::new();
+    context.insert("jogadores", &jogadores);
+    let page_content = TEMPLATES.render("admin/jogadores.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Render New Jogador Form", skip(_admin))]
+#[get("/admin/jogadores/new")]
+pub async fn jogador_new_form(_admin: AdminUser) -> Result<impl Responder> {
+    let context = tera::Context::new();
+    let page_content = TEMPLATES.render("admin/jogador_form.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[derive(Deserialize, Debug)]
+struct NewJogadorData {
+    nome: String,
+    apelido: String,
+    email: String,
+    password: SecretString,
+    #[serde(default)]
+    admin: Option<String>,
+}
+
+#[tracing::instrument(
+    name = "Create Jogador",
+    skip(state, _admin, data),
+    fields(email = %data.email)
+)]
+#[post("/admin/jogadores")]
+pub async fn jogador_create(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    data: web::Form<NewJogadorData>,
+) -> Result<HttpResponse> {
+    let db = &state.db;
+    let data = data.into_inner();
+    let email = data.email.trim();
+
+    if data.nome.trim().is_empty() || data.apelido.trim().is_empty() || email.is_empty() {
+        return Ok(create_bad_request("Name, nickname and email are required"));
+    }
+    if data.password.expose_secret().len() < 8 {
+        return Ok(create_bad_request("Password must have at least 8 characters"));
+    }
+    let existing = Jogador::find()
+        .filter(jogador::Column::Email.eq(email))
+        .one(db)
+        .await?;
+    if existing.is_some() {
+        return Ok(create_bad_request("Email already registered"));
+    }
+
+    let senha_hash = hash_password(&data.password)?;
+
+    // Jogadores criados por um admin não precisam verificar o email
+    let novo = jogador::ActiveModel {
+        nome: ActiveValue::Set(data.nome.trim().to_string()),
+        apelido: ActiveValue::Set(data.apelido.trim().to_string()),
+        email: ActiveValue::Set(email.to_string()),
+        senha_hash: ActiveValue::Set(senha_hash),
+        admin: ActiveValue::Set(data.admin.is_some()),
+        imagem: ActiveValue::Set(None),
+        ativo: ActiveValue::Set(true),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    tracing::info!("Created jogador {}", novo.id);
+
+    Ok(HttpResponse::Ok()
+        .append_header(("HX-Redirect", "/admin/jogadores"))
+        .body("Jogador criado"))
+}
+
+#[tracing::instrument(name = "Render Edit Jogador Form", skip(state, _admin))]
+#[get("/admin/jogadores/{id}")]
+pub async fn jogador_edit_form(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    id: Path<i32>,
+) -> Result<HttpResponse> {
+    let Some(jogador) = Jogador::find_by_id(id.into_inner()).one(&state.db).await? else {
+        return Ok(HttpResponse::NotFound().body("Jogador not found"));
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("jogador", &JogadorAdmin::from(jogador));
+    let page_content = TEMPLATES.render("admin/jogador_form.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[derive(Deserialize, Debug)]
+struct EditJogadorData {
+    nome: String,
+    apelido: String,
+    email: String,
+    #[serde(default)]
+    admin: Option<String>,
+    #[serde(default)]
+    ativo: Option<String>,
+}
+
+#[tracing::instrument(name = "Update Jogador", skip(state, admin, data))]
+#[post("/admin/jogadores/{id}")]
+pub async fn jogador_update(
+    state: Data<AppState>,
+    AdminUser(admin): AdminUser,
+    id: Path<i32>,
+    data: web::Form<EditJogadorData>,
+) -> Result<HttpResponse> {
+    let db = &state.db;
+    let id = id.into_inner();
+    let data = data.into_inner();
+    let email = data.email.trim();
+
+    if data.nome.trim().is_empty() || data.apelido.trim().is_empty() || email.is_empty() {
+        return Ok(create_bad_request("Name, nickname and email are required"));
+    }
+    // Evita que o admin se tranque fora do painel
+    if admin.id == id && (data.admin.is_none() || data.ativo.is_none()) {
+        return Ok(create_bad_request("You can't remove your own admin access"));
+    }
+    let same_email = Jogador::find()
+        .filter(jogador::Column::Email.eq(email))
+        .filter(jogador::Column::Id.ne(id))
+        .one(db)
+        .await?;
+    if same_email.is_some() {
+        return Ok(create_bad_request("Email already registered"));
+    }
+
+    let jogador = Jogador::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or(anyhow::anyhow!("Jogador not found"))?;
+    let mut jogador = jogador.into_active_model();
+    jogador.nome = ActiveValue::Set(data.nome.trim().to_string());
+    jogador.apelido = ActiveValue::Set(data.apelido.trim().to_string());
+    jogador.email = ActiveValue::Set(email.to_string());
+    jogador.admin = ActiveValue::Set(data.admin.is_some());
+    jogador.ativo = ActiveValue::Set(data.ativo.is_some());
+    jogador.update(db).await?;
+    tracing::info!("Updated jogador {}", id);
+
+    Ok(HttpResponse::Ok()
+        .append_header(("HX-Redirect", "/admin/jogadores"))
+        .body("Jogador atualizado"))
+}
+
+#[tracing::instrument(name = "Deactivate Jogador", skip(state, admin))]
+#[post("/admin/jogadores/{id}/deactivate")]
+pub async fn jogador_deactivate(
+    state: Data<AppState>,
+    AdminUser(admin): AdminUser,
+    id: Path<i32>,
+) -> Result<HttpResponse> {
+    let db = &state.db;
+    let id = id.into_inner();
+    if admin.id == id {
+        return Ok(create_bad_request("You can't deactivate yourself"));
+    }
+
+    let jogador = Jogador::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or(anyhow::anyhow!("Jogador not found"))?;
+    let mut jogador = jogador.into_active_model();
+    jogador.ativo = ActiveValue::Set(false);
+    jogador.update(db).await?;
+    tracing::info!("Deactivated jogador {}", id);
+
+    Ok(HttpResponse::Ok()
+        .append_header(("HX-Refresh", "true"))
+        .body("Jogador desativado"))
+}
+
+#[tracing::instrument(name = "Remove Jogador Image", skip(state, _admin))]
+#[post("/admin/jogadores/{id}/remove_image")]
+pub async fn jogador_remove_image(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    id: Path<i32>,
+) -> Result<HttpResponse> {
+    let db = &state.db;
+    let id = id.into_inner();
+
+    let jogador = Jogador::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or(anyhow::anyhow!("Jogador not found"))?;
+    let mut jogador = jogador.into_active_model();
+    jogador.imagem = ActiveValue::Set(None);
+    jogador.update(db).await?;
+    tracing::info!("Removed image of jogador {}", id);
+
+    Ok(HttpResponse::Ok()
+        .append_header(("HX-Refresh", "true"))
+        .body("Imagem removida"))
+}
+
+#[derive(Serialize, Debug)]
+struct JogoAdmin {
+    id: i32,
+    nome: String,
+    local: String,
+    data: String,
+    hora: String,
+    capacidade: i32,
+    tamanho_cedula: i32,
+    cancelado: bool,
+}
+
+impl From<jogo::Model> for JogoAdmin {
+    fn from(j: jogo::Model) -> Self {
+        JogoAdmin {
+            id: j.id,
+            nome: j.nome,
+            local: j.local,
+            data: j.data.format("%Y-%m-%d").to_string(),
+            hora: j.hora.format("%H:%M").to_string(),
+            capacidade: j.capacidade,
+            tamanho_cedula: j.tamanho_cedula,
+            cancelado: j.cancelado,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct JogoData {
+    nome: String,
+    local: String,
+    data: String,
+    hora: String,
+    capacidade: String,
+    tamanho_cedula: String,
+    #[serde(default)]
+    cancelado: Option<String>,
+}
+
+/// Valores já validados do formulário de jogo.
+struct JogoValidado {
+    nome: String,
+    local: String,
+    data: NaiveDate,
+    hora: NaiveTime,
+    capacidade: i32,
+    tamanho_cedula: i32,
+}
+
+fn validate_jogo(data: &JogoData) -> std::result::Result<JogoValidado, &'static str> {
+    let nome = data.nome.trim();
+    let local = data.local.trim();
+    if nome.is_empty() || local.is_empty() {
+        return Err("Name and location are required");
+    }
+    let Ok(dia) = NaiveDate::parse_from_str(data.data.trim(), "%Y-%m-%d") else {
+        return Err("Invalid date");
+    };
+    let Ok(hora) = NaiveTime::parse_from_str(data.hora.trim(), "%H:%M") else {
+        return Err("Invalid time");
+    };
+    let capacidade = match data.capacidade.trim().parse::<i32>() {
+        Ok(c) if c > 0 => c,
+        _ => return Err("Capacity must be a positive number"),
+    };
+    let tamanho_cedula = match data.tamanho_cedula.trim().parse::<i32>() {
+        Ok(t) if jogos::validar_tamanho_cedula(t).is_ok() => t,
+        _ => return Err("Ballot size must be between 2 and 10"),
+    };
+    Ok(JogoValidado {
+        nome: nome.to_string(),
+        local: local.to_string(),
+        data: dia,
+        hora,
+        capacidade,
+        tamanho_cedula,
+    })
+}
+
+#[tracing::instrument(name = "Render Admin Jogos", skip(state, _admin))]
+#[get("/admin/jogos")]
+pub async fn jogos_page(
+    state: Data<AppState>,
+    _admin: AdminUser,
+) -> Result<impl Responder> {
+    let jogos = Jogo::find()
+        .order_by_desc(jogo::Column::Data)
+        .order_by_desc(jogo::Column::Hora)
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .map(JogoAdmin::from)
+        .collect::<Vec<_>>();
+
+    let mut context = tera::Context::new();
+    context.insert("jogos", &jogos);
+    let page_content = TEMPLATES.render("admin/jogos.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Render New Jogo Form", skip(_admin))]
+#[get("/admin/jogos/new")]
+pub async fn jogo_new_form(_admin: AdminUser) -> Result<impl Responder> {
+    let mut context = tera::Context::new();
+    context.insert("capacidade_padrao", &jogos::CAPACIDADE_PADRAO);
+    context.insert("tamanho_cedula_padrao", &jogos::TAMANHO_CEDULA_PADRAO);
+    let page_content = TEMPLATES.render("admin/jogo_form.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Create Jogo", skip(state, _admin, data))]
+#[post("/admin/jogos")]
+pub async fn jogo_create(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    data: web::Form<JogoData>,
+) -> Result<HttpResponse> {
+    let data = match validate_jogo(&data) {
+        Ok(data) => data,
+        Err(msg) => return Ok(create_bad_request(msg)),
+    };
+
+    let novo = jogo::ActiveModel {
+        nome: ActiveValue::Set(data.nome),
+        local: ActiveValue::Set(data.local),
+        data: ActiveValue::Set(data.data),
+        hora: ActiveValue::Set(data.hora),
+        capacidade: ActiveValue::Set(data.capacidade),
+        tamanho_cedula: ActiveValue::Set(data.tamanho_cedula),
+        cancelado: ActiveValue::Set(false),
+        ..Default::default()
+    }
+    .insert(&state.db)
+    .await?;
+    tracing::info!("Created jogo {}", novo.id);
+
+    Ok(HttpResponse::Ok()
+        .append_header(("HX-Redirect", "/admin/jogos"))
+        .body("Jogo criado"))
+}
+
+#[tracing::instrument(name = "Render Edit Jogo Form", skip(state, _admin))]
+#[get("/admin/jogos/{id}")]
+pub async fn jogo_edit_form(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    id: Path<i32>,
+) -> Result<HttpResponse> {
+    let Some(jogo) = Jogo::find_by_id(id.into_inner()).one(&state.db).await? else {
+        return Ok(HttpResponse::NotFound().body("Jogo not found"));
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("jogo", &JogoAdmin::from(jogo));
+    let page_content = TEMPLATES.render("admin/jogo_form.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Update Jogo", skip(state, _admin, data))]
+#[post("/admin/jogos/{id}")]
+pub async fn jogo_update(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    id: Path<i32>,
+    data: web::Form<JogoData>,
+) -> Result<HttpResponse> {
+    let db = &state.db;
+    let id = id.into_inner();
+    let cancelado = data.cancelado.is_some();
+    let data = match validate_jogo(&data) {
+        Ok(data) => data,
+        Err(msg) => return Ok(create_bad_request(msg)),
+    };
+
+    let jogo = Jogo::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or(anyhow::anyhow!("Jogo not found"))?;
+    let mut jogo = jogo.into_active_model();
+    jogo.nome = ActiveValue::Set(data.nome);
+    jogo.local = ActiveValue::Set(data.local);
+    jogo.data = ActiveValue::Set(data.data);
+    jogo.hora = ActiveValue::Set(data.hora);
+    jogo.capacidade = ActiveValue::Set(data.capacidade);
+    jogo.tamanho_cedula = ActiveValue::Set(data.tamanho_cedula);
+    jogo.cancelado = ActiveValue::Set(cancelado);
+    jogo.update(db).await?;
+    tracing::info!("Updated jogo {}", id);
+
+    Ok(HttpResponse::Ok()
+        .append_header(("HX-Redirect", "/admin/jogos"))
+        .body("Jogo atualizado"))
+}
+
+#[tracing::instrument(name = "Cancel Jogo", skip(state, _admin))]
+#[post("/admin/jogos/{id}/cancel")]
+pub async fn jogo_cancel(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    id: Path<i32>,
+) -> Result<HttpResponse> {
+    let db = &state.db;
+    let id = id.into_inner();
+
+    let jogo = Jogo::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or(anyhow::anyhow!("Jogo not found"))?;
+    let mut jogo = jogo.into_active_model();
+    jogo.cancelado = ActiveValue::Set(true);
+    jogo.update(db).await?;
+    tracing::info!("Cancelled jogo {}", id);
+
+    Ok(HttpResponse::Ok()
+        .append_header(("HX-Refresh", "true"))
+        .body("Jogo cancelado"))
+}
+
+#[derive(Serialize, Debug)]
+struct ListaExtraEntry {
+    id: i32,
+    jogador_id: i32,
+    nome: String,
+    data: String,
+}
+
+#[derive(Serialize, Debug)]
+struct JogadorElegivel {
+    id: i32,
+    nome: String,
+    apelido: String,
+    lista_extra: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct ListaExtraSemana {
+    week_id: i32,
+    inicio: String,
+    fim: String,
+    entries: Vec<ListaExtraEntry>,
+    elegiveis: Vec<JogadorElegivel>,
+}
+
+#[derive(Serialize, Debug)]
+struct EfeitoListaExtra {
+    week_id: i32,
+    antes: usize,
+    depois: usize,
+    adicionados: Vec<String>,
+    removidos: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct WeekQuery {
+    week_id: Option<i32>,
+}
+
+/// Intervalo de datas da lista extra que conta para a votação da semana,
+/// isto é, o jogo que abre a votação. Vai do ponto de referência da semana
+/// anterior, que nem sempre está 7×24h antes, ao desta semana.
+fn lista_extra_window(week_id: i32) -> (DateTime<Utc>, DateTime<Utc>) {
+    (ref_point_from_id(week_id - 1), ref_point_from_id(week_id))
+}
+
+fn format_local(date: DateTime<Utc>) -> String {
+    date.with_timezone(&timings::timezone()).format("%d/%m/%Y %H:%M").to_string()
+}
+
+async fn lista_extra_semana(state: &AppState, week_id: i32) -> Result<ListaExtraSemana> {
+    let db = &state.db;
+    let (inicio, fim) = lista_extra_window(week_id);
+
+    let entries = ListaExtra::find()
+        .find_also_related(Jogador)
+        .filter(lista_extra::Column::Data.gt(inicio))
+        .filter(lista_extra::Column::Data.lt(fim))
+        .order_by_asc(lista_extra::Column::Data)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(entry, jogador)| ListaExtraEntry {
+            id: entry.id,
+            jogador_id: entry.jogador_id,
+            nome: jogador.map(|j| j.nome).unwrap_or_default(),
+            data: format_local(entry.data.and_utc()),
+        })
+        .collect::<Vec<_>>();
+
+    let extra_ids = entries.iter().map(|e| e.jogador_id).collect::<Vec<_>>();
+    let elegiveis = eligibility::elegiveis(state, Escopo::Semana(fim))
+        .await?
+        .into_iter()
+        .map(|j| JogadorElegivel {
+            lista_extra: extra_ids.contains(&j.id),
+            id: j.id,
+            nome: j.nome,
+            apelido: j.apelido,
+        })
+        .collect();
+
+    Ok(ListaExtraSemana {
+        week_id,
+        inicio: format_local(inicio),
+        fim: format_local(fim),
+        entries,
+        elegiveis,
+    })
+}
+
+/// Compara os elegíveis antes e depois de uma mudança na lista extra.
+fn efeito(
+    week_id: i32,
+    antes: &[jogador::Model],
+    depois: &[jogador::Model],
+) -> EfeitoListaExtra {
+    let adicionados = depois
+        .iter()
+        .filter(|j| !antes.iter().any(|a| a.id == j.id))
+        .map(|j| j.nome.clone())
+        .collect();
+    let removidos = antes
+        .iter()
+        .filter(|j| !depois.iter().any(|d| d.id == j.id))
+        .map(|j| j.nome.clone())
+        .collect();
+    EfeitoListaExtra {
+        week_id,
+        antes: antes.len(),
+        depois: depois.len(),
+        adicionados,
+        removidos,
+    }
+}
+
+#[tracing::instrument(name = "Render Admin Lista Extra", skip(state, _admin))]
+#[get("/admin/lista_extra")]
+pub async fn lista_extra_page(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    query: web::Query<WeekQuery>,
+) -> Result<impl Responder> {
+    let week_id = query.week_id.unwrap_or_else(|| ref_point_id(Utc::now()));
+    let semana = lista_extra_semana(&state, week_id).await?;
+    let ativos = Jogador::find()
+        .filter(jogador::Column::Ativo.eq(true))
+        .order_by_asc(jogador::Column::Nome)
+        .all(&state.db)
+        .await?
+        .into_iter()
+        .map(JogadorAdmin::from)
+        .collect::<Vec<_>>();
+    let (_, fim) = lista_extra_window(week_id);
+
+    let mut context = tera::Context::new();
+    context.insert("semana", &semana);
+    context.insert("jogadores", &ativos);
+    context.insert(
+        "data_padrao",
+        &(fim - chrono::Duration::hours(1))
+            .with_timezone(&timings::timezone())
+            .format("%Y-%m-%dT%H:%M")
+            .to_string(),
+    );
+    let page_content = TEMPLATES.render("admin/lista_extra.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Get Lista Extra", skip(state, _admin))]
+#[get("/api/admin/lista_extra")]
+pub async fn lista_extra_json(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    query: web::Query<WeekQuery>,
+) -> Result<impl Responder> {
+    let week_id = query.week_id.unwrap_or_else(|| ref_point_id(Utc::now()));
+    Ok(HttpResponse::Ok().json(lista_extra_semana(&state, week_id).await?))
+}
+
+#[derive(Deserialize, Debug)]
+struct NovaEntradaListaExtra {
+    jogador_id: i32,
+    /// Data e hora local, no formato de um `<input type="datetime-local">`
+    data: String,
+}
+
+async fn add_lista_extra(
+    state: &AppState,
+    entrada: &NovaEntradaListaExtra,
+) -> Result<std::result::Result<EfeitoListaExtra, &'static str>> {
+    let db = &state.db;
+    let Ok(data) = NaiveDateTime::parse_from_str(&entrada.data, "%Y-%m-%dT%H:%M") else {
+        return Ok(Err("Invalid date"));
+    };
+    let Some(data) = timings::timezone().from_local_datetime(&data).earliest() else {
+        return Ok(Err("Invalid date"));
+    };
+    let data = data.with_timezone(&Utc);
+    if Jogador::find_by_id(entrada.jogador_id).one(db).await?.is_none() {
+        return Ok(Err("Jogador not found"));
+    }
+
+    // A entrada vale para a votação que começa depois da data escolhida
+    let week_id = ref_point_id(data) + 1;
+    let (_, fim) = lista_extra_window(week_id);
+    let antes = eligibility::elegiveis(state, Escopo::Semana(fim)).await?;
+
+    lista_extra::ActiveModel {
+        jogador_id: ActiveValue::Set(entrada.jogador_id),
+        data: ActiveValue::Set(data.naive_utc()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    tracing::info!("Added jogador {} to lista extra at {}", entrada.jogador_id, data);
+
+    let depois = eligibility::elegiveis(state, Escopo::Semana(fim)).await?;
+    Ok(Ok(efeito(week_id, &antes, &depois)))
+}
+
+async fn remove_lista_extra(state: &AppState, id: i32) -> Result<Option<EfeitoListaExtra>> {
+    let db = &state.db;
+    let Some(entry) = ListaExtra::find_by_id(id).one(db).await? else {
+        return Ok(None);
+    };
+
+    let week_id = ref_point_id(entry.data.and_utc()) + 1;
+    let (_, fim) = lista_extra_window(week_id);
+    let antes = eligibility::elegiveis(state, Escopo::Semana(fim)).await?;
+
+    entry.delete(db).await?;
+    tracing::info!("Removed lista extra entry {}", id);
+
+    let depois = eligibility::elegiveis(state, Escopo::Semana(fim)).await?;
+    Ok(Some(efeito(week_id, &antes, &depois)))
+}
+
+fn render_efeito(efeito: &EfeitoListaExtra) -> Result<HttpResponse> {
+    let mut context = tera::Context::new();
+    context.insert("efeito", efeito);
+    let page_content = TEMPLATES.render("admin/shards/lista_extra_efeito.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Add Lista Extra", skip(state, _admin))]
+#[post("/admin/lista_extra")]
+pub async fn lista_extra_add(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    entrada: web::Form<NovaEntradaListaExtra>,
+) -> Result<HttpResponse> {
+    match add_lista_extra(&state, &entrada).await? {
+        Ok(efeito) => render_efeito(&efeito),
+        Err(message) => Ok(create_bad_request(message)),
+    }
+}
+
+#[tracing::instrument(name = "Add Lista Extra JSON", skip(state, _admin))]
+#[post("/api/admin/lista_extra")]
+pub async fn lista_extra_add_json(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    entrada: web::Json<NovaEntradaListaExtra>,
+) -> Result<HttpResponse> {
+    match add_lista_extra(&state, &entrada).await? {
+        Ok(efeito) => Ok(HttpResponse::Ok().json(efeito)),
+        Err(message) => Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": message }))),
+    }
+}
+
+#[tracing::instrument(name = "Remove Lista Extra", skip(state, _admin))]
+#[post("/admin/lista_extra/{id}/delete")]
+pub async fn lista_extra_remove(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    id: Path<i32>,
+) -> Result<HttpResponse> {
+    match remove_lista_extra(&state, id.into_inner()).await? {
+        Some(efeito) => render_efeito(&efeito),
+        None => Ok(create_bad_request("Entry not found")),
+    }
+}
+
+#[tracing::instrument(name = "Remove Lista Extra JSON", skip(state, _admin))]
+#[delete("/api/admin/lista_extra/{id}")]
+pub async fn lista_extra_remove_json(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    id: Path<i32>,
+) -> Result<HttpResponse> {
+    match remove_lista_extra(&state, id.into_inner()).await? {
+        Some(efeito) => Ok(HttpResponse::Ok().json(efeito)),
+        None => Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "Entry not found" }))),
+    }
+}
@@ -7,16 +7,46 @@ use actix_web::http::header::CacheControl;
 use actix_web::web::{Data, Path};
 use actix_web::{get, post, web, HttpMessage, HttpResponse, Responder};
 use argon2;
-use argon2::PasswordVerifier;
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use argon2::{PasswordHasher, PasswordVerifier};
 use sea_orm::{
     ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter,
+    TransactionTrait,
 };
 use secrecy::{ExposeSecret, SecretBox, SecretString};
 use serde::Deserialize;
 
 use crate::error::create_bad_request;
+use crate::guards::CurrentUser;
+use crate::tokens::{self, TOKEN_RESET_SENHA, TOKEN_VERIFICACAO};
+use crate::mail;
 use futures_util::StreamExt as _;
 
+/// Tempo que o link de verificação enviado no cadastro continua válido.
+const VALIDADE_VERIFICACAO: chrono::Duration = chrono::Duration::hours(48);
+/// Tempo que o link de troca de senha continua válido.
+const VALIDADE_RESET_SENHA: chrono::Duration = chrono::Duration::hours(1);
+
+pub(crate) fn hash_password(password: &SecretString) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = argon2::Argon2::default()
+        .hash_password(password.expose_secret().as_bytes(), &salt)
+        .map_err(|_| anyhow::anyhow!("Failed to hash password"))?
+        .to_string();
+    Ok(hash)
+}
+
+/// Confere as regras de senha, retornando a mensagem de erro se inválida.
+fn check_new_password(password: &SecretString, confirm: &SecretString) -> Option<&'static str> {
+    if password.expose_secret().len() < 8 {
+        return Some("Password must have at least 8 characters");
+    }
+    if password.expose_secret() != confirm.expose_secret() {
+        return Some("Passwords do not match");
+    }
+    None
+}
+
 #[derive(Deserialize, Debug)]
 struct LoginData {
     email: String,
@@ -55,6 +85,12 @@ pub async fn login(
             .verify_password(login_data.password.expose_secret().as_bytes(), &parsed_hash)
             .is_ok()
         {
+            if !user.ativo {
+                tracing::info!("User {} has not verified the email yet", user.email);
+                return Ok(create_bad_request(
+                    "Account is not active, check your email for the verification link",
+                ));
+            }
             tracing::info!("Logged in user {}", user.email);
             Identity::login(&request.extensions(), user.email.clone()).unwrap();
             Ok(HttpResponse::Ok()
@@ -70,6 +106,231 @@ pub async fn login(
     }
 }
 
+#[derive(Deserialize, Debug)]
+struct RegisterData {
+    nome: String,
+    apelido: String,
+    email: String,
+    password: SecretString,
+    password_confirm: SecretString,
+}
+
+#[tracing::instrument(name = "Render Register Form")]
+#[get("/register")]
+pub async fn register_form() -> Result<impl Responder> {
+    let context = tera::Context::new();
+    let page_content = TEMPLATES.render("register.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(
+    name = "Register User",
+    skip(app_state, register_data),
+    fields(email = %register_data.email)
+)]
+#[post("/register")]
+pub async fn register(
+    app_state: Data<AppState>,
+    register_data: web::Form<RegisterData>,
+) -> Result<HttpResponse> {
+    let db = &app_state.db;
+    let register_data = register_data.into_inner();
+    let nome = register_data.nome.trim();
+    let apelido = register_data.apelido.trim();
+    let email = register_data.email.trim();
+
+    if nome.is_empty() || apelido.is_empty() || email.is_empty() {
+        return Ok(create_bad_request("Name, nickname and email are required"));
+    }
+    if let Some(message) =
+        check_new_password(&register_data.password, &register_data.password_confirm)
+    {
+        return Ok(create_bad_request(message));
+    }
+
+    let existing = Jogador::find()
+        .filter(jogador::Column::Email.eq(email))
+        .one(db)
+        .await?;
+    if existing.is_some() {
+        tracing::info!("Email already registered");
+        return Ok(create_bad_request("Email already registered"));
+    }
+
+    // A conta é ligada ao email usado nas inscrições, é por ele que a
+    // elegibilidade para a votação é calculada.
+    if !app_state.roster.email_conhecido(email).await? {
+        tracing::info!("Email not found in the roster");
+        return Ok(create_bad_request(
+            "No ticket found for this email, use the same email you use to sign up for the games",
+        ));
+    }
+
+    let senha_hash = hash_password(&register_data.password)?;
+
+    let user = jogador::ActiveModel {
+        nome: ActiveValue::Set(nome.to_string()),
+        apelido: ActiveValue::Set(apelido.to_string()),
+        email: ActiveValue::Set(email.to_string()),
+        senha_hash: ActiveValue::Set(senha_hash),
+        admin: ActiveValue::Set(false),
+        imagem: ActiveValue::Set(None),
+        ativo: ActiveValue::Set(false),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    let token = tokens::create_token(db, user.id, TOKEN_VERIFICACAO, VALIDADE_VERIFICACAO).await?;
+    app_state
+        .mailer
+        .send(&mail::Email {
+            para: user.email.clone(),
+            assunto: "FuteINF - Confirme seu email".to_string(),
+            corpo: format!(
+                "Olá {},\n\nPara ativar sua conta no FuteINF, abra o link abaixo:\n{}/verify/{}\n\nO link vale por 48 horas.",
+                user.nome, app_state.public_url, token
+            ),
+        })
+        .await?;
+
+    let mut context = tera::Context::new();
+    context.insert("email", &user.email);
+    let page_content = TEMPLATES.render("shards/register_sent.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Verify Email", skip(app_state, token))]
+#[get("/verify/{token}")]
+pub async fn verify_email(app_state: Data<AppState>, token: Path<String>) -> Result<HttpResponse> {
+    let db = &app_state.db;
+    let jogador_id = tokens::consume_token(db, TOKEN_VERIFICACAO, &token.into_inner()).await?;
+
+    let mut context = tera::Context::new();
+    if let Some(jogador_id) = jogador_id {
+        let user = Jogador::find_by_id(jogador_id)
+            .one(db)
+            .await?
+            .ok_or(anyhow::anyhow!("User not found"))?;
+        tracing::info!("Activating user {}", user.email);
+        let mut user = user.into_active_model();
+        user.ativo = ActiveValue::Set(true);
+        user.update(db).await?;
+        context.insert("verificado", &true);
+    } else {
+        tracing::info!("Invalid or expired verification token");
+        context.insert("verificado", &false);
+    }
+    let page_content = TEMPLATES.render("verify.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[derive(Deserialize, Debug)]
+struct ForgotPasswordData {
+    email: String,
+}
+
+#[tracing::instrument(name = "Render Forgot Password Form")]
+#[get("/forgot_password")]
+pub async fn forgot_password_form() -> Result<impl Responder> {
+    let context = tera::Context::new();
+    let page_content = TEMPLATES.render("forgot_password.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(
+    name = "Forgot Password",
+    skip(app_state, forgot_data),
+    fields(email = %forgot_data.email)
+)]
+#[post("/forgot_password")]
+pub async fn forgot_password(
+    app_state: Data<AppState>,
+    forgot_data: web::Form<ForgotPasswordData>,
+) -> Result<HttpResponse> {
+    let db = &app_state.db;
+    let user = Jogador::find()
+        .filter(jogador::Column::Email.eq(forgot_data.email.trim()))
+        .filter(jogador::Column::Ativo.eq(true))
+        .one(db)
+        .await?;
+
+    // A resposta é a mesma exista ou não a conta, para não revelar quais emails estão cadastrados
+    if let Some(user) = user {
+        let token = tokens::create_token(db, user.id, TOKEN_RESET_SENHA, VALIDADE_RESET_SENHA).await?;
+        app_state
+            .mailer
+            .send(&mail::Email {
+                para: user.email.clone(),
+                assunto: "FuteINF - Troca de senha".to_string(),
+                corpo: format!(
+                    "Olá {},\n\nPara escolher uma nova senha no FuteINF, abra o link abaixo:\n{}/reset_password/{}\n\nO link vale por 1 hora e só pode ser usado uma vez. Se você não pediu a troca, ignore este email.",
+                    user.nome, app_state.public_url, token
+                ),
+            })
+            .await?;
+        tracing::info!("Password reset email sent");
+    } else {
+        tracing::info!("No active user for password reset");
+    }
+
+    let page_content = TEMPLATES.render("shards/forgot_password_sent.html", &tera::Context::new())?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[derive(Deserialize, Debug)]
+struct ResetPasswordData {
+    password: SecretString,
+    password_confirm: SecretString,
+}
+
+#[tracing::instrument(name = "Render Reset Password Form", skip(token))]
+#[get("/reset_password/{token}")]
+pub async fn reset_password_form(token: Path<String>) -> Result<impl Responder> {
+    let mut context = tera::Context::new();
+    context.insert("token", &token.into_inner());
+    let page_content = TEMPLATES.render("reset_password.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Reset Password", skip(app_state, token, reset_data))]
+#[post("/reset_password/{token}")]
+pub async fn reset_password(
+    app_state: Data<AppState>,
+    token: Path<String>,
+    reset_data: web::Form<ResetPasswordData>,
+) -> Result<HttpResponse> {
+    let db = &app_state.db;
+    if let Some(message) = check_new_password(&reset_data.password, &reset_data.password_confirm) {
+        return Ok(create_bad_request(message));
+    }
+
+    let senha_hash = hash_password(&reset_data.password)?;
+
+    let txn = db.begin().await?;
+    let Some(jogador_id) = tokens::consume_token(&txn, TOKEN_RESET_SENHA, &token.into_inner()).await?
+    else {
+        tracing::info!("Invalid or expired password reset token");
+        return Ok(create_bad_request("This link is invalid, was already used or has expired"));
+    };
+
+    let user = Jogador::find_by_id(jogador_id)
+        .one(&txn)
+        .await?
+        .ok_or(anyhow::anyhow!("User not found"))?;
+    tracing::info!("Resetting password of {}", user.email);
+    let mut user = user.into_active_model();
+    user.senha_hash = ActiveValue::Set(senha_hash);
+    user.update(&txn).await?;
+    // Os outros links de troca pedidos antes deixam de valer
+    tokens::invalidate_tokens(&txn, jogador_id, TOKEN_RESET_SENHA).await?;
+    txn.commit().await?;
+
+    Ok(HttpResponse::Ok()
+        .append_header(("HX-Redirect", "/login"))
+        .body("Password changed"))
+}
+
 #[tracing::instrument(name = "Logout User", skip(identity))]
 #[post("/logout")]
 pub async fn logout(identity: Option<Identity>) -> impl Responder {
@@ -81,12 +342,12 @@ pub async fn logout(identity: Option<Identity>) -> impl Responder {
         .body("Logged out")
 }
 
-#[tracing::instrument(name = "Upload Image", skip(app_state, payload, identity))]
+#[tracing::instrument(name = "Upload Image", skip(app_state, payload, user))]
 #[post("/upload_image")]
 pub async fn upload_image(
     app_state: Data<AppState>,
     mut payload: actix_multipart::Multipart,
-    identity: Option<Identity>,
+    CurrentUser(user): CurrentUser,
 ) -> Result<HttpResponse> {
     let db = &app_state.db;
     let mut imagem = None;
@@ -102,11 +363,6 @@ pub async fn upload_image(
         imagem = Some(data);
     }
     if let Some(imagem) = imagem {
-        let user = Jogador::find()
-            .filter(jogador::Column::Email.eq(identity.unwrap().id().unwrap()))
-            .one(db)
-            .await?;
-        let user = user.ok_or(anyhow::anyhow!("User not found"))?;
         let mut user = user.into_active_model();
         user.imagem = ActiveValue::set(Some(imagem));
         user.save(db).await?;
@@ -0,0 +1,89 @@
+use crate::error::{create_bad_request, Result};
+use crate::leaderboard::{leaderboard, Classificado, Leaderboard, MINIMO_PADRAO};
+use crate::templates::TEMPLATES;
+use crate::timings::{self, ref_point_from_id};
+use crate::AppState;
+use actix_web::web::Data;
+use actix_web::{get, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug)]
+struct LeaderboardQuery {
+    /// Primeira semana, inclusive; sem ela, desde a primeira apuração.
+    de: Option<i32>,
+    /// Última semana, inclusive; sem ela, até a última apuração.
+    ate: Option<i32>,
+    minimo: Option<usize>,
+}
+
+async fn carregar(state: &AppState, query: &LeaderboardQuery) -> Result<Option<Leaderboard>> {
+    if let (Some(de), Some(ate)) = (query.de, query.ate) {
+        if de > ate {
+            return Ok(None);
+        }
+    }
+    let minimo = query.minimo.unwrap_or(MINIMO_PADRAO);
+    Ok(Some(leaderboard(&state.db, query.de, query.ate, minimo).await?))
+}
+
+fn data_semana(week_id: i32) -> String {
+    ref_point_from_id(week_id)
+        .with_timezone(&timings::timezone())
+        .format("%d/%m/%Y")
+        .to_string()
+}
+
+#[derive(Serialize, Debug)]
+struct ClassificadoView {
+    #[serde(flatten)]
+    classificado: Classificado,
+    melhor_data: String,
+    pior_data: String,
+}
+
+#[tracing::instrument(name = "Render Leaderboard", skip(state))]
+#[get("/leaderboard")]
+pub async fn leaderboard_page(
+    query: web::Query<LeaderboardQuery>,
+    state: Data<AppState>,
+) -> Result<HttpResponse> {
+    let Some(leaderboard) = carregar(&state, &query).await? else {
+        return Ok(create_bad_request("The first week must not be after the last one"));
+    };
+
+    let jogadores = leaderboard
+        .jogadores
+        .into_iter()
+        .map(|classificado| ClassificadoView {
+            melhor_data: data_semana(classificado.melhor.week_id),
+            pior_data: data_semana(classificado.pior.week_id),
+            classificado,
+        })
+        .collect::<Vec<_>>();
+
+    let mut context = tera::Context::new();
+    context.insert("de", &leaderboard.de);
+    context.insert("ate", &leaderboard.ate);
+    context.insert("minimo", &leaderboard.minimo);
+    context.insert("semanas", &leaderboard.semanas.len());
+    context.insert("inicio", &leaderboard.semanas.first().map(|w| data_semana(*w)));
+    context.insert("fim", &leaderboard.semanas.last().map(|w| data_semana(*w)));
+    context.insert("jogadores", &jogadores);
+    context.insert("abaixo_do_minimo", &leaderboard.abaixo_do_minimo);
+    let page_content = TEMPLATES.render("leaderboard.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Get Leaderboard", skip(state))]
+#[get("/api/leaderboard")]
+pub async fn leaderboard_json(
+    query: web::Query<LeaderboardQuery>,
+    state: Data<AppState>,
+) -> Result<HttpResponse> {
+    match carregar(&state, &query).await? {
+        Some(leaderboard) => Ok(HttpResponse::Ok().json(leaderboard)),
+        None => Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "The first week must not be after the last one"
+        }))),
+    }
+}
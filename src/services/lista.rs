@@ -1,31 +1,37 @@
 use crate::templates::TEMPLATES;
-use crate::timings::ref_point_id;
-use crate::{list, AppState};
-use actix_identity::Identity;
-use actix_web::web::Data;
-use actix_web::{get, HttpResponse, Responder};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use crate::timings::{self, ref_point_id};
 use crate::entities::{prelude::*, *};
+use crate::error::create_bad_request;
+use crate::{inscricoes, jogos, mail, AppState};
+use crate::guards::CurrentUser;
+use actix_web::web::{Data, Path};
+use actix_web::{get, post, HttpResponse, Responder};
+use sea_orm::EntityTrait;
 use chrono::Utc;
-use anyhow::anyhow;
+use serde::Serialize;
 
-#[tracing::instrument(name = "Render Index", skip(identity, state))]
+#[derive(Serialize, Debug)]
+struct ProximoJogo {
+    id: i32,
+    nome: String,
+    local: String,
+    data: String,
+    capacidade: i32,
+    cancelado: bool,
+}
+
+#[tracing::instrument(name = "Render Index", skip(state, user))]
 #[get("/")]
-pub async fn index(identity: Option<Identity>, 
-    state: Data<AppState>
+pub async fn index(
+    state: Data<AppState>,
+    user: Option<CurrentUser>,
 ) -> crate::error::Result<impl Responder> {
     let mut context = tera::Context::new();
-    if let Some(identity) = identity {
+    if let Some(CurrentUser(user)) = user {
         context.insert("logged_in", &true);
-        let user: Option<jogador::Model> = Jogador::find()
-            .filter(jogador::Column::Email.eq(identity.id().unwrap()))
-            .one(&state.db.clone())
-            .await?;
-        let user = user.ok_or(anyhow!("User not found"))?;
         context.insert("username", &user.nome);
         context.insert("user_id", &user.id);
-
-
+        context.insert("is_admin", &user.admin);
     } else {
         context.insert("logged_in", &false);
         context.insert("username", &"");
@@ -33,6 +39,24 @@ pub async fn index(identity: Option<Identity>,
     let week_id = ref_point_id(Utc::now());
     let last_week = week_id - 1;
     context.insert("last_week", &last_week);
+
+    let proximos = jogos::proximos_jogos(&state.db, Utc::now())
+        .await?
+        .into_iter()
+        .map(|j| ProximoJogo {
+            id: j.id,
+            data: jogos::kickoff(&j)
+                .with_timezone(&timings::timezone())
+                .format("%d/%m/%Y %H:%M")
+                .to_string(),
+            nome: j.nome,
+            local: j.local,
+            capacidade: j.capacidade,
+            cancelado: j.cancelado,
+        })
+        .collect::<Vec<_>>();
+    context.insert("jogos", &proximos);
+
     let page_content = TEMPLATES.render("index.html", &context)?;
     Ok(HttpResponse::Ok().body(page_content))
 }
@@ -41,8 +65,112 @@ pub async fn index(identity: Option<Identity>,
 #[get("/jogadores")]
 pub async fn jogadores(state: Data<AppState>) -> crate::error::Result<impl Responder> {
     let mut context = tera::Context::new();
-    context.insert("jogadores", &list::get_list(state.alfio_db.clone()).await?);
-    context.insert("max_jogadores", &list::get_max_jogadores());
+    match jogos::proximo_jogo(&state.db, Utc::now()).await? {
+        Some(jogo) => {
+            context.insert("jogadores", &state.roster.lista_do_jogo(&jogo).await?);
+            context.insert("max_jogadores", &jogo.capacidade);
+        }
+        None => {
+            context.insert("jogadores", &Vec::<crate::list::Jogador>::new());
+            context.insert("max_jogadores", &jogos::CAPACIDADE_PADRAO);
+        }
+    }
     let page_content = TEMPLATES.render("shards/jogadores.html", &context)?;
     Ok(HttpResponse::Ok().body(page_content))
 }
+
+async fn find_jogo(state: &AppState, jogo_id: i32) -> crate::error::Result<Option<jogo::Model>> {
+    Ok(Jogo::find_by_id(jogo_id).one(&state.db).await?)
+}
+
+async fn render_lista(
+    state: &AppState,
+    jogo: &jogo::Model,
+    jogador_id: Option<i32>,
+) -> crate::error::Result<HttpResponse> {
+    let lista = inscricoes::lista_do_jogo(&state.db, jogo).await?;
+    let aberta = !jogo.cancelado && jogos::kickoff(jogo) > Utc::now();
+
+    let mut context = tera::Context::new();
+    context.insert("jogo_id", &jogo.id);
+    context.insert("capacidade", &jogo.capacidade);
+    context.insert("confirmados", &lista.confirmados);
+    context.insert("espera", &lista.espera);
+    context.insert("aberta", &aberta);
+    context.insert("logged_in", &jogador_id.is_some());
+    context.insert(
+        "inscrito",
+        &jogador_id.and_then(|id| lista.posicao(id)).is_some(),
+    );
+    let page_content = TEMPLATES.render("shards/lista_jogo.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+#[tracing::instrument(name = "Render Lista Jogo", skip(state, user))]
+#[get("/jogo/{jogo_id}/lista")]
+pub async fn lista_jogo(
+    state: Data<AppState>,
+    jogo_id: Path<i32>,
+    user: Option<CurrentUser>,
+) -> crate::error::Result<HttpResponse> {
+    let Some(jogo) = find_jogo(&state, jogo_id.into_inner()).await? else {
+        return Ok(HttpResponse::NotFound().body("Game not found"));
+    };
+    render_lista(&state, &jogo, user.map(|CurrentUser(u)| u.id)).await
+}
+
+#[tracing::instrument(name = "Join Jogo", skip(state, user))]
+#[post("/jogo/{jogo_id}/inscrever")]
+pub async fn inscrever(
+    state: Data<AppState>,
+    jogo_id: Path<i32>,
+    CurrentUser(user): CurrentUser,
+) -> crate::error::Result<HttpResponse> {
+    let Some(jogo) = find_jogo(&state, jogo_id.into_inner()).await? else {
+        return Ok(create_bad_request("Game not found"));
+    };
+    if jogo.cancelado {
+        return Ok(create_bad_request("This game was cancelled"));
+    }
+    if jogos::kickoff(&jogo) <= Utc::now() {
+        return Ok(create_bad_request("This game has already started"));
+    }
+
+    inscricoes::inscrever(&state.db, jogo.id, user.id).await?;
+    render_lista(&state, &jogo, Some(user.id)).await
+}
+
+#[tracing::instrument(name = "Leave Jogo", skip(state, user))]
+#[post("/jogo/{jogo_id}/sair")]
+pub async fn sair(
+    state: Data<AppState>,
+    jogo_id: Path<i32>,
+    CurrentUser(user): CurrentUser,
+) -> crate::error::Result<HttpResponse> {
+    let Some(jogo) = find_jogo(&state, jogo_id.into_inner()).await? else {
+        return Ok(create_bad_request("Game not found"));
+    };
+    if jogos::kickoff(&jogo) <= Utc::now() {
+        return Ok(create_bad_request("This game has already started"));
+    }
+
+    let promovido = inscricoes::sair(&state.db, &jogo, user.id).await?;
+    if let Some(promovido) = promovido {
+        let data = jogos::kickoff(&jogo)
+            .with_timezone(&timings::timezone())
+            .format("%d/%m/%Y %H:%M");
+        let email = mail::Email {
+            para: promovido.email.clone(),
+            assunto: format!("FuteINF - Você está na lista do jogo {}", jogo.nome),
+            corpo: format!(
+                "Olá {},\n\nAbriu uma vaga e você saiu da lista de espera do jogo {} ({}, {}).\nSe não puder ir, saia da lista pelo site para liberar a vaga.",
+                promovido.nome, jogo.nome, jogo.local, data
+            ),
+        };
+        // A vaga já é dele, falhar em avisar não deve impedir a saída do outro jogador
+        if let Err(e) = state.mailer.send(&email).await {
+            tracing::error!("Failed to notify {} of promotion: {:?}", promovido.email, e);
+        }
+    }
+    render_lista(&state, &jogo, Some(user.id)).await
+}
@@ -1,4 +1,7 @@
+pub mod admin;
 pub mod auth;
+pub mod leaderboard;
 pub mod lista;
+pub mod perfil;
 pub mod ranking;
 pub mod voting;
@@ -0,0 +1,48 @@
+use crate::entities::prelude::*;
+use crate::error::Result;
+use crate::perfil::{perfil, sparkline};
+use crate::templates::TEMPLATES;
+use crate::timings::{self, ref_point_from_id};
+use crate::AppState;
+use actix_web::web::Data;
+use actix_web::{get, web, HttpResponse};
+use sea_orm::EntityTrait;
+
+#[tracing::instrument(name = "Render Player Profile", skip(state))]
+#[get("/jogador/{id}")]
+pub async fn perfil_jogador(id: web::Path<i32>, state: Data<AppState>) -> Result<HttpResponse> {
+    let Some(jogador) = Jogador::find_by_id(id.into_inner()).one(&state.db).await? else {
+        return Ok(HttpResponse::NotFound().body("Player not found"));
+    };
+    let perfil = perfil(&state, &jogador).await?;
+
+    let historico = perfil
+        .historico
+        .iter()
+        .map(|h| {
+            serde_json::json!({
+                "week_id": h.week_id,
+                "semana": ref_point_from_id(h.week_id)
+                    .with_timezone(&timings::timezone())
+                    .format("%d/%m/%Y")
+                    .to_string(),
+                "pos": h.pos,
+                "de": h.de,
+                "media": h.media,
+            })
+        })
+        .rev()
+        .collect::<Vec<_>>();
+    let medias = perfil.historico.iter().map(|h| h.media).collect::<Vec<_>>();
+
+    let mut context = tera::Context::new();
+    context.insert("id", &jogador.id);
+    context.insert("nome", &jogador.nome);
+    context.insert("apelido", &jogador.apelido);
+    context.insert("historico", &historico);
+    context.insert("sparkline", &sparkline(&medias, 240, 48));
+    context.insert("cedulas", &perfil.cedulas);
+    context.insert("presenca", &perfil.presenca);
+    let page_content = TEMPLATES.render("perfil.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
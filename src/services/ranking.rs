@@ -1,241 +1,201 @@
-use crate::entities::ballot;
 use crate::error::Result;
-use crate::ranking::{get_or_create_apuracao, RankingEntry};
+use crate::guards::AdminUser;
+use crate::entities::apuracao;
+use crate::entities::sea_orm_active_enums::ApuracaoState;
+use crate::error::create_bad_request;
+use crate::ranking::{
+    get_or_create_apuracao, get_or_create_apuracao_jogo, pontuar_semana, previa_semana, Nota,
+    PoderDeVoto, Ranking,
+};
+use crate::scoring;
 use crate::templates::TEMPLATES;
-use crate::timings::{self, get_ref_point_of, ref_point_from_id, ref_point_id};
-use crate::AppState;
+use crate::timings::{self, ref_point_from_id, ref_point_id};
+use crate::{jogos, AppState};
 use actix_web::web::Data;
 use actix_web::{get, web, HttpResponse, Responder};
 use chrono::prelude::*;
-use itertools::Itertools;
-use tracing::{info, Instrument};
+use tracing::Instrument;
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use actix_identity::Identity;
-use anyhow::anyhow;
-use crate::entities::{prelude::*, *};
-
-#[tracing::instrument(name = "Render Debug Ranking", skip(identity, state))]
-#[get("/debugRanking")]
-pub async fn debug_ranking(
-    state: Data<AppState>, 
-    identity: Option<Identity>
-) -> Result<impl Responder> {
-    let identity = identity.ok_or(anyhow!("Not logged in"))?;
-
-    // Check if the user is an admin
-    let user = Jogador::find()
-        .filter(jogador::Column::Email.eq(&identity.id().unwrap()))
-        .one(&state.db)
-        .await?
-        .ok_or(anyhow!("User not found"))?;
-
-    if user.admin != true {
-        tracing::info!("User is not an admin");
-        Err(anyhow!("User is not an admin"))?;
-    }
-        
-
-
-    let db = &state.db;
-
-    let now = Utc::now();
-
-    // Get the start of the week, ie. the last monday
-    let start_of_week = get_ref_point_of(now);
-
-    // COMPUTE RANKING
-    // Get all votes this week
-    let votes = Ballot::find()
-        .filter(ballot::Column::State.contains("closed"))
-        .filter(ballot::Column::Date.gt(start_of_week))
-        .all(db)
-        .await?;
-
-    // Count how many votes each voter has cast
-    let mut votes_per_voter = HashMap::new();
-    for vote in &votes {
-        let voter = vote.voter.clone();
-        let count = votes_per_voter.entry(voter).or_insert(0);
-        *count += 1;
-    }
-
-    info!("Votes per voter: {:?}", votes_per_voter);
-
-    const MAX_ACC_VOTING_POWER: f32 = 2.5;
-    let vote_power = votes_per_voter
-        .iter()
-        .map(|(v, count)| {
-            if (*count as f32) <= MAX_ACC_VOTING_POWER {
-                // Voting power can be 1 for each vote and the sum of the votes
-                // can't be greater than MAX_ACC_VOTING_POWER
-                (v, 1.)
-            } else {
-                // Cap the voting power to MAX_ACC_VOTING_POWER/number_of_votes
-                (v, MAX_ACC_VOTING_POWER / (*count as f32))
-            }
-        })
-        .collect::<HashMap<_, _>>();
-
-    info!("Vote power: {:?}", vote_power);
-
-    struct Vote {
-        vote: f32,
-        weight: f32,
-    }
-
-    // Compute the ranking
-    let mut votes_per_player: HashMap<i32, Vec<(f32, f32)>> = HashMap::new();
-    for vote in &votes {
-        // Two categories of votes: "ranked" and "unranked"
-        // Ranked votes are counted as beat/4 points
-        // Where beat is the number of players that are ranked lower than the player plus the unranked votes
-        // Unranked votes are counted as (number_of_unranked_votes - 1)/(4*2) points
-        let ranked_votes = vote
-            .vote
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.as_i64().unwrap() as i32)
-            .collect::<Vec<_>>();
-        let all_players = vote
-            .players
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|x| x.as_i64().unwrap() as i32)
-            .collect::<Vec<_>>();
-        let unranked_votes = all_players
-            .iter()
-            .filter(|x| !ranked_votes.contains(x))
-            .collect::<Vec<_>>();
-        let unranked_votes_count = unranked_votes.len() as f32;
-        const NUMBER_OF_PLAYERS_IN_VOTE: usize = 4;
-        let weight = vote_power.get(&vote.voter).unwrap();
-        let mut ranked_votes = ranked_votes
-            .iter()
-            .enumerate()
-            .map(|(i, player)| {
-                let beat = NUMBER_OF_PLAYERS_IN_VOTE - i; //
-                let vote = (beat as f32) / (NUMBER_OF_PLAYERS_IN_VOTE as f32);
-                (
-                    *player,
-                    Vote {
-                        vote,
-                        weight: *weight,
-                    },
-                )
-            })
-            .collect::<Vec<_>>();
-
-        let unranked_votes_value =
-            (unranked_votes_count - 1.) / (NUMBER_OF_PLAYERS_IN_VOTE as f32 * 2.);
-        for player in unranked_votes {
-            ranked_votes.push((
-                *player,
-                Vote {
-                    vote: unranked_votes_value,
-                    weight: *weight,
-                },
-            ));
-        }
-
-        for (player, vote) in ranked_votes {
-            let entry = votes_per_player.entry(player).or_default();
-            entry.push((vote.vote, vote.weight));
-        }
-    }
-
-    info!("Votes per player: {:?}", votes_per_player);
-
-    let mean: HashMap<i32, f32> = votes_per_player
-        .iter()
-        .map(|(k, v)| {
-            let sum = v.iter().map(|(vote, weight)| vote * weight).sum::<f32>();
-            let weight = v.iter().map(|(_, weight)| weight).sum::<f32>();
-            (*k, sum / weight)
-        })
-        .collect();
-
-    info!("Mean: {:?}", mean);
+use crate::entities::prelude::*;
+
+#[derive(Serialize, Debug)]
+struct JogadorPrevia {
+    pos: i32,
+    id: i32,
+    nome: String,
+    media: f32,
+    desvio_padrao: Option<f32>,
+    notas: Vec<Nota>,
+}
 
-    let std_dev: HashMap<i32, f32> = votes_per_player
-        .iter()
-        .map(|(k, v)| {
-            let mean = mean.get(k).unwrap();
-            let sum = v
-                .iter()
-                .map(|(vote, weight)| (vote - mean).powi(2) * weight)
-                .sum::<f32>();
-            let weight = v.iter().map(|(_, weight)| weight).sum::<f32>();
-            let non_zero_weight_votes = v.iter().filter(|(vote, weight)| *weight > 0.).count();
-            if non_zero_weight_votes == 0 {
-                (*k, 0.)
-            } else {
-                let denominator =
-                    ((non_zero_weight_votes as f32 - 1.) * weight) / non_zero_weight_votes as f32;
-                (*k, (sum / denominator).sqrt())
-            }
-        })
-        .collect();
+#[derive(Serialize, Debug)]
+struct GrupoPrevia {
+    titulo: String,
+    votos: i32,
+    /// Vazio quando a estratégia não é a atual.
+    poder_de_voto: Option<Vec<PoderDeVoto>>,
+    jogadores: Vec<JogadorPrevia>,
+}
 
-    info!("Std Dev: {:?}", std_dev);
+#[tracing::instrument(name = "Render Debug Ranking", skip(_admin, state))]
+#[get("/debugRanking")]
+pub async fn debug_ranking(state: Data<AppState>, _admin: AdminUser) -> Result<impl Responder> {
+    render_debug_ranking(&state, ref_point_id(Utc::now())).await
+}
 
-    let all_players = Jogador::find().all(db).await?;
+#[tracing::instrument(name = "Render Debug Week Ranking", skip(_admin, state))]
+#[get("/debugRanking/{week_id}")]
+pub async fn debug_ranking_week(
+    week_id: web::Path<i32>,
+    state: Data<AppState>,
+    _admin: AdminUser,
+) -> Result<impl Responder> {
+    render_debug_ranking(&state, week_id.into_inner()).await
+}
 
-    let mut players_mentioned = all_players
-        .iter()
-        .filter(|x| votes_per_player.contains_key(&x.id))
-        .map(|x| {
-            let mean = mean.get(&x.id).unwrap();
-            let std_dev = std_dev.get(&x.id).unwrap();
-            let votes = votes_per_player.get(&x.id).unwrap().iter().count();
-            RankingEntry {
-                pos: 0,
-                nome: x.nome.clone(),
-                media: *mean,
-                votos: votes as i32,
-                desvio_padrao: std_dev.is_finite().then_some(*std_dev),
-                id: x.id,
-            }
-        })
-        .collect::<Vec<_>>();
-    for (i, player) in players_mentioned
-        .iter_mut()
-        .sorted_by(|a, b| b.media.partial_cmp(&a.media).unwrap())
-        .enumerate()
-    {
-        player.pos = (i + 1) as i32;
+/// Prévia ao vivo do ranking de uma semana, com os dados intermediários de
+/// cada jogo.
+async fn render_debug_ranking(state: &AppState, week_id: i32) -> Result<HttpResponse> {
+    let tz = timings::timezone();
+    let (ranking, grupos) = previa_semana(&state.db, week_id, state.scoring.as_ref()).await?;
+
+    let mut view = vec![];
+    for grupo in grupos {
+        let titulo = match grupo.jogo_id {
+            Some(jogo_id) => match Jogo::find_by_id(jogo_id).one(&state.db).await? {
+                Some(jogo) => format!("Jogo {}", jogo.nome),
+                None => format!("Jogo #{}", jogo_id),
+            },
+            None => "Votação semanal".to_string(),
+        };
+        let (poder_de_voto, mut notas) = match grupo.detalhes {
+            Some(detalhes) => (Some(detalhes.poder_de_voto), detalhes.notas),
+            None => (None, HashMap::new()),
+        };
+        view.push(GrupoPrevia {
+            titulo,
+            votos: grupo.ranking.votes,
+            poder_de_voto,
+            jogadores: grupo
+                .ranking
+                .entries
+                .into_iter()
+                .map(|e| JogadorPrevia {
+                    notas: notas.remove(&e.id).unwrap_or_default(),
+                    pos: e.pos,
+                    id: e.id,
+                    nome: e.nome,
+                    media: e.media,
+                    desvio_padrao: e.desvio_padrao,
+                })
+                .collect(),
+        });
     }
 
-    let players_mentioned = players_mentioned
-        .iter()
-        .sorted_by(|a, b| a.pos.cmp(&b.pos))
-        .collect_vec();
-
-
     let mut context = tera::Context::new();
-    context.insert("votes", &votes.len());
+    context.insert("votes", &ranking.votes);
+    context.insert("estrategia", state.scoring.nome());
     context.insert(
         "last_reset",
-        &start_of_week
-            .with_timezone(&Local)
+        &ref_point_from_id(week_id)
+            .with_timezone(&tz)
             .format("%d/%m/%Y %H:%M:%S")
             .to_string(),
     );
     context.insert(
         "now",
-        &Utc::now()
-            .with_timezone(&Local)
+        &ranking
+            .timestamp
+            .with_timezone(&tz)
             .format("%d/%m/%Y %H:%M:%S")
             .to_string(),
     );
-    context.insert("ranking", &players_mentioned);
-    context.insert("ref_point_id", &ref_point_id(now));
+    context.insert("ranking", &ranking.entries);
+    context.insert("grupos", &view);
+    context.insert("ref_point_id", &week_id);
     let page_content = TEMPLATES.render("debug_ranking.html", &context)?;
     Ok(HttpResponse::Ok().body(page_content))
 }
 
+#[derive(Deserialize, Debug)]
+struct RescoreQuery {
+    estrategia: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct Comparacao {
+    id: i32,
+    nome: String,
+    pos_base: Option<i32>,
+    media_base: Option<f32>,
+    pos: Option<i32>,
+    media: Option<f32>,
+    /// Quantas posições o jogador subiu com a nova estratégia.
+    delta: Option<i32>,
+}
+
+/// Compara a apuração de uma semana com o resultado de outra estratégia.
+#[tracing::instrument(name = "Render Rescore", skip(_admin, state))]
+#[get("/admin/rescore/{week_id}")]
+pub async fn rescore_week(
+    week_id: web::Path<i32>,
+    query: web::Query<RescoreQuery>,
+    state: Data<AppState>,
+    _admin: AdminUser,
+) -> Result<HttpResponse> {
+    let week_id = week_id.into_inner();
+    let nome = query.estrategia.as_deref().unwrap_or("borda");
+    let Some(estrategia) = scoring::estrategia(nome) else {
+        return Ok(create_bad_request(format!("Unknown scoring strategy {nome}")));
+    };
+
+    // A base é a apuração gravada; sem ela, a estratégia configurada
+    let apuracao = Apuracao::find()
+        .filter(apuracao::Column::WeekId.eq(week_id))
+        .filter(apuracao::Column::State.eq(ApuracaoState::Complete))
+        .one(&state.db)
+        .await?;
+    let (base, apurada) = match apuracao {
+        Some(apuracao) => (serde_json::from_value::<Ranking>(apuracao.results)?, true),
+        None => (pontuar_semana(&state.db, week_id, state.scoring.as_ref()).await?, false),
+    };
+    let nova = pontuar_semana(&state.db, week_id, estrategia.as_ref()).await?;
+
+    let base_por_id = base.entries.iter().map(|e| (e.id, e)).collect::<HashMap<_, _>>();
+    let nova_por_id = nova.entries.iter().map(|e| (e.id, e)).collect::<HashMap<_, _>>();
+    let comparacao = base
+        .entries
+        .iter()
+        .chain(nova.entries.iter().filter(|e| !base_por_id.contains_key(&e.id)))
+        .map(|e| {
+            let b = base_por_id.get(&e.id);
+            let n = nova_por_id.get(&e.id);
+            Comparacao {
+                id: e.id,
+                nome: e.nome.clone(),
+                pos_base: b.map(|b| b.pos),
+                media_base: b.map(|b| b.media),
+                pos: n.map(|n| n.pos),
+                media: n.map(|n| n.media),
+                delta: b.zip(n).map(|(b, n)| b.pos - n.pos),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut context = tera::Context::new();
+    context.insert("week_id", &week_id);
+    context.insert("apurada", &apurada);
+    context.insert("base", &base.estrategia);
+    context.insert("estrategia", &nova.estrategia);
+    context.insert("estrategias", &scoring::ESTRATEGIAS);
+    context.insert("votes", &nova.votes);
+    context.insert("comparacao", &comparacao);
+    let page_content = TEMPLATES.render("admin/rescore.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
 #[tracing::instrument(name = "Render Week Ranking", skip(state))]
 #[get("/week_ranking/{week_id}")]
 pub async fn week_ranking(
@@ -245,24 +205,40 @@ pub async fn week_ranking(
     let week_id = week_id.into_inner();
     let ref_point = ref_point_from_id(week_id);
     let semana = ref_point
-        .with_timezone(&Local)
+        .with_timezone(&timings::timezone())
         .format("%d/%m/%Y %H:%M:%S")
         .to_string();
 
+    let jogos_semana = jogos::jogos_da_semana(&state.db, week_id).await?;
+    let jogos_context = jogos_semana
+        .iter()
+        .map(|j| {
+            serde_json::json!({
+                "id": j.id,
+                "nome": j.nome,
+                "data": jogos::kickoff(j)
+                    .with_timezone(&timings::timezone())
+                    .format("%d/%m/%Y %H:%M")
+                    .to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+
     // Checa se ja é possivel ver o ranking da semana
-    if !timings::publish_results(ref_point) {
+    let publication_time = jogos::publish_time_semana(week_id, &jogos_semana);
+    if Utc::now() < publication_time {
 
         tracing::info!("Week {} not published yet", week_id);
         // Não é possivel ver o ranking da semana
-        let publication_time = timings::publish_time(ref_point);
         let publication_time = publication_time
-            .with_timezone(&Local)
+            .with_timezone(&timings::timezone())
             .format("%d/%m/%Y %H:%M:%S")
             .to_string();
         let mut context = tera::Context::new();
         context.insert("semana", &semana);
         context.insert("week_id", &week_id);
         context.insert("data_publicacao", &publication_time);
+        context.insert("jogos", &jogos_context);
 
         let page_content = TEMPLATES.render("week_ranking_not_published.html", &context)?;
         return Ok(HttpResponse::Ok().body(page_content));
@@ -281,14 +257,71 @@ pub async fn week_ranking(
         "gerado",
         &ranking
             .timestamp
-            .with_timezone(&Local)
+            .with_timezone(&timings::timezone())
             .format("%d/%m/%Y %H:%M:%S")
             .to_string(),
     );
     context.insert("ranking", &ranking.entries);
     context.insert("votes", &ranking.votes);
     context.insert("week_id", &week_id);
+    context.insert("jogos", &jogos_context);
 
     let page_content = TEMPLATES.render("week_ranking.html", &context)?;
     Ok(HttpResponse::Ok().body(page_content))
 }
+
+#[tracing::instrument(name = "Render Game Ranking", skip(state))]
+#[get("/jogo_ranking/{jogo_id}")]
+pub async fn jogo_ranking(
+    jogo_id: web::Path<i32>,
+    state: Data<AppState>,
+) -> Result<impl Responder> {
+    let jogo_id = jogo_id.into_inner();
+    let Some(jogo) = Jogo::find_by_id(jogo_id).one(&state.db).await? else {
+        return Ok(HttpResponse::NotFound().body("Game not found"));
+    };
+    let kickoff = jogos::kickoff(&jogo);
+
+    let mut context = tera::Context::new();
+    context.insert("jogo", &jogo.nome);
+    context.insert("local", &jogo.local);
+    context.insert(
+        "data",
+        &kickoff
+            .with_timezone(&timings::timezone())
+            .format("%d/%m/%Y %H:%M")
+            .to_string(),
+    );
+    context.insert("week_id", &jogos::week_id(&jogo));
+
+    if !timings::publish_results_jogo(kickoff) {
+        tracing::info!("Game {} not published yet", jogo_id);
+        context.insert(
+            "data_publicacao",
+            &timings::publish_time_jogo(kickoff)
+                .with_timezone(&timings::timezone())
+                .format("%d/%m/%Y %H:%M:%S")
+                .to_string(),
+        );
+        let page_content = TEMPLATES.render("jogo_ranking.html", &context)?;
+        return Ok(HttpResponse::Ok().body(page_content));
+    }
+
+    let ranking = get_or_create_apuracao_jogo(state.clone(), jogo_id)
+        .instrument(tracing::info_span!("Get or create apuracao jogo"))
+        .await?;
+
+    context.insert(
+        "gerado",
+        &ranking
+            .timestamp
+            .with_timezone(&timings::timezone())
+            .format("%d/%m/%Y %H:%M:%S")
+            .to_string(),
+    );
+    context.insert("ranking", &ranking.entries);
+    context.insert("votes", &ranking.votes);
+
+    let page_content = TEMPLATES.render("jogo_ranking.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
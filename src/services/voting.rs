@@ -1,20 +1,26 @@
 use crate::error::{Error, Result, create_bad_request};
 use crate::templates::TEMPLATES;
+use crate::eligibility::{self, Escopo};
 use crate::timings::{
-    can_cast_vote, can_create_ballot, get_end_elegible_check, get_ref_point_of,
-    get_start_elegible_check, ref_point_from_id, ref_point_id,
+    self, can_cast_vote, can_cast_vote_jogo, can_create_ballot, can_create_ballot_jogo,
+    ref_point_from_id, ref_point_id,
 };
-use crate::{entities, list, AppState};
-use actix_identity::Identity;
+use crate::guards::{AdminUser, CurrentUser};
+use crate::{entities, jogos, list, sorteio, AppState};
 use actix_web::web::Data;
 use actix_web::{get, post, web, HttpResponse, Responder};
 use anyhow::anyhow;
 use entities::{prelude::*, *};
+use entities::sea_orm_active_enums::BallotState;
 use tracing::{info, warn, Instrument, error};
-use rand::prelude::SliceRandom;
-use sea_orm::{error, ActiveModelTrait, ActiveValue, ColumnTrait, EntityTrait, QueryFilter, SelectColumns};
-use serde::Deserialize;
-use sqlx::{prelude::*, query};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    error, ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
 
 #[tracing::instrument(name = "Render Voting Form", skip(state))]
 #[get("/voting")]
@@ -24,98 +30,190 @@ pub async fn voting(state: Data<AppState>) -> Result<impl Responder> {
     Ok(HttpResponse::Ok().body(page_content))
 }
 
-#[tracing::instrument(name = "Render Elegible Players", skip(state, identity))]
+#[tracing::instrument(name = "Render Elegible Players", skip(state, _user))]
 #[get("/elegible")]
 pub async fn get_elegible_players(
     state: Data<AppState>,
-    identity: Option<Identity>,
+    _user: CurrentUser,
 ) -> Result<impl Responder> {
-    identity.ok_or(anyhow!("Not logged in"))?;
-    let db = &state.db;
-    let now = chrono::Utc::now();
+    let elegible_players = eligibility::elegiveis(&state, Escopo::Semana(chrono::Utc::now())).await?;
 
-    let start_elegible_check = get_start_elegible_check(now);
-    let end_elegible_check = get_end_elegible_check(now);
+    Ok(HttpResponse::Ok().json(
+        elegible_players
+            .iter()
+            .map(|x| x.apelido.to_owned())
+            .collect::<Vec<String>>(),
+    ))
+}
 
-    let all_players: Vec<_> = Jogador::find()
-        .select_column(jogador::Column::Id)
-        .select_column(jogador::Column::Nome)
-        .select_column(jogador::Column::Apelido)
-        .all(db)
-        .await?;
+#[derive(Deserialize, Debug)]
+struct ExplainQuery {
+    week_id: Option<i32>,
+    jogo_id: Option<i32>,
+}
 
-    // Filter for players that have been active in the last 30 days
-    let players: Vec<_> = query!(
-        "select distinct email_address as email FROM ticket WHERE event_id IN 
-        (SELECT id FROM event WHERE start_ts between $1 and $2)
-         and email_address is not null;",
-        start_elegible_check,
-        end_elegible_check
-    )
-    .fetch_all(&state.alfio_db)
-    
-    .await?;
-
-    let extra_players = ListaExtra::find()
-        .filter(lista_extra::Column::Data.gt(start_elegible_check))
-        .filter(lista_extra::Column::Data.lt(end_elegible_check))
-        .all(db)
-        .await?;
+#[derive(Serialize, Debug)]
+struct AvaliacaoView {
+    id: i32,
+    nome: String,
+    apelido: String,
+    elegivel: bool,
+    convidado: bool,
+    pode_votar: bool,
+    motivos: Vec<String>,
+    impedimentos: Vec<String>,
+    impedimentos_voto: Vec<String>,
+}
 
-    let extra_players = extra_players
-        .iter()
-        .map(|f| f.jogador_id)
-        .collect::<Vec<_>>();
+/// Mostra, para cada jogador, se ele pode ser votado e por quê.
+#[tracing::instrument(name = "Render Elegible Explain", skip(state, _admin))]
+#[get("/elegible/explain")]
+pub async fn explain_elegible_players(
+    state: Data<AppState>,
+    _admin: AdminUser,
+    query: web::Query<ExplainQuery>,
+) -> Result<impl Responder> {
+    let tz = timings::timezone();
+    let jogo = match query.jogo_id {
+        Some(jogo_id) => Some(
+            Jogo::find_by_id(jogo_id)
+                .one(&state.db)
+                .await?
+                .ok_or(anyhow!("Game not found"))?,
+        ),
+        None => None,
+    };
+    let week_id = match &jogo {
+        Some(jogo) => jogos::week_id(jogo),
+        None => query.week_id.unwrap_or_else(|| ref_point_id(chrono::Utc::now())),
+    };
+    let escopo = match &jogo {
+        Some(jogo) => Escopo::Jogo(jogo),
+        None => Escopo::Semana(ref_point_from_id(week_id)),
+    };
+    let (inicio, fim) = eligibility::janela(escopo);
 
-    let players = players
-        .iter()
-        .map(|f| f.email.to_owned())
-        .collect::<Option<Vec<_>>>()
-        .unwrap_or_default();
-    let elegible_players = all_players
-        .iter()
-        .filter(|x| players.contains(&x.email) || extra_players.contains(&x.id))
+    let avaliacoes = eligibility::avaliar(&state, escopo)
+        .await?
+        .into_iter()
+        .map(|a| AvaliacaoView {
+            elegivel: a.elegivel(),
+            convidado: a.convidado,
+            pode_votar: a.pode_votar(),
+            id: a.jogador.id,
+            nome: a.jogador.nome,
+            apelido: a.jogador.apelido,
+            motivos: a.motivos.iter().map(|m| m.to_string()).collect(),
+            impedimentos: a.impedimentos.iter().map(|i| i.to_string()).collect(),
+            impedimentos_voto: a.impedimentos_voto.iter().map(|i| i.to_string()).collect(),
+        })
         .collect::<Vec<_>>();
 
-    info!(
-        "Elegible players({}): {:?}",
-        elegible_players.len(),
-        elegible_players.iter().map(|x| x.nome.to_owned()).collect::<Vec<String>>()
+    let mut context = tera::Context::new();
+    context.insert("week_id", &week_id);
+    context.insert("jogo", &jogo.as_ref().map(|j| j.nome.clone()));
+    context.insert("jogos", &jogos::jogos_da_semana(&state.db, week_id)
+        .await?
+        .into_iter()
+        .map(|j| serde_json::json!({ "id": j.id, "nome": j.nome }))
+        .collect::<Vec<_>>());
+    context.insert("inicio", &inicio.with_timezone(&tz).format("%d/%m/%Y %H:%M").to_string());
+    context.insert("fim", &fim.with_timezone(&tz).format("%d/%m/%Y %H:%M").to_string());
+    context.insert(
+        "total_elegiveis",
+        &avaliacoes.iter().filter(|a| a.elegivel).count(),
     );
+    context.insert("avaliacoes", &avaliacoes);
+    let page_content = TEMPLATES.render("admin/elegible_explain.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
 
-    Ok(HttpResponse::Ok().json(
-        elegible_players
-            .iter()
-            .map(|x| x.apelido.to_owned())
-            .collect::<Vec<String>>(),
-    ))
+#[derive(Deserialize, Debug)]
+struct CreateVoting {
+    jogo_id: Option<i32>,
 }
 
-#[tracing::instrument(name = "Create Voting", skip(state, identity))]
+#[tracing::instrument(name = "Render Game Chooser", skip(state, _user))]
+#[get("/voting/jogos")]
+pub async fn voting_choose_jogo(
+    state: Data<AppState>,
+    _user: CurrentUser,
+) -> Result<impl Responder> {
+    let tz = timings::timezone();
+    let jogos = jogos::jogos_em_votacao(&state.db, chrono::Utc::now())
+        .await?
+        .into_iter()
+        .map(|j| {
+            let fim = timings::get_end_create_ballot_jogo(jogos::kickoff(&j));
+            serde_json::json!({
+                "id": j.id,
+                "nome": j.nome,
+                "local": j.local,
+                "data": jogos::kickoff(&j).with_timezone(&tz).format("%d/%m/%Y %H:%M").to_string(),
+                "fim": fim.with_timezone(&tz).format("%d/%m/%Y %H:%M").to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut context = tera::Context::new();
+    context.insert("jogos", &jogos);
+    let page_content = TEMPLATES.render("escolher_jogo.html", &context)?;
+    Ok(HttpResponse::Ok().body(page_content))
+}
+
+/// Cria uma cédula para o jogo escolhido. Sem `jogo_id`, usa o único jogo com
+/// votação aberta, manda escolher se houver mais de um, ou cai na votação
+/// semanal antiga se não houver nenhum jogo cadastrado em votação.
+#[tracing::instrument(name = "Create Voting", skip(state, user))]
 #[post("/voting/create")]
 pub async fn voting_create(
     state: Data<AppState>,
-    identity: Option<Identity>,
+    CurrentUser(user): CurrentUser,
+    query: web::Query<CreateVoting>,
 ) -> Result<impl Responder> {
-    let identity = identity.ok_or(anyhow!("Not logged in"))?;
-
     let db = &state.db;
 
     let now = chrono::Utc::now();
-    let ref_point = get_ref_point_of(now);
-    let event_id = ref_point_id(now);
 
-    // Check if a open ballot exists for the current week
+    let jogo = match query.jogo_id {
+        Some(jogo_id) => {
+            let jogo = Jogo::find_by_id(jogo_id).one(db).await?;
+            match jogo {
+                Some(jogo) => Some(jogo),
+                None => return Ok(create_bad_request("Game not found")),
+            }
+        }
+        None => {
+            let mut abertos = jogos::jogos_em_votacao(db, now).await?;
+            if abertos.len() > 1 {
+                return Ok(HttpResponse::Ok()
+                    .append_header(("HX-Redirect", "/voting/jogos"))
+                    .body("Choose a game"));
+            }
+            abertos.pop()
+        }
+    };
+
+    let event_id = match &jogo {
+        Some(jogo) => jogos::week_id(jogo),
+        None => ref_point_id(now),
+    };
+
+    // Check if a open ballot exists for the current week or game
     let alredy_open_ballot = Ballot::find()
         .filter(ballot::Column::FuteId.eq(event_id))
-        .filter(ballot::Column::State.eq("open"))
-        .filter(ballot::Column::Voter.eq(identity.id().unwrap()))
+        .filter(match &jogo {
+            Some(jogo) => ballot::Column::JogoId.eq(jogo.id),
+            None => ballot::Column::JogoId.is_null(),
+        })
+        .filter(ballot::Column::State.eq(BallotState::Generated))
+        .filter(ballot::Column::Voter.eq(&user.email))
         .one(db)
         .await?;
 
     if let Some(b) = alredy_open_ballot {
         // Send the user to the voting page
-        info!("User {} already has an open ballot", identity.id().unwrap());
+        info!("User {} already has an open ballot", user.email);
         return Ok(HttpResponse::Ok()
             .append_header(("HX-Redirect", format!("/voting/{}", b.id)))
             .body("Voting created"));
@@ -123,65 +221,67 @@ pub async fn voting_create(
 
     // Check if we can create a ballot
 
-    let can_create_ballot = can_create_ballot(now);
+    let can_create_ballot = match &jogo {
+        Some(jogo) => can_create_ballot_jogo(jogos::kickoff(jogo), now),
+        None => can_create_ballot(now),
+    };
     if !can_create_ballot {
         return Ok(create_bad_request("Voting is closed"));
     }
-    let start_elegible_check = get_start_elegible_check(now);
-    let end_elegible_check = get_end_elegible_check(now);
-
-    let all_players: Vec<jogador::Model> = Jogador::find().all(db).await?;
-
-    // Filter for players that have been active in the last 30 days
-    let players: Vec<_> = query!(
-        "select distinct email_address as email FROM ticket WHERE event_id IN 
-        (SELECT id FROM event WHERE start_ts between $1 and $2)
-         and email_address is not null;",
-        start_elegible_check,
-        end_elegible_check
-    )
-    .fetch_all(&state.alfio_db)
-    .await?;
-
-    let extra_players = ListaExtra::find()
-        .filter(lista_extra::Column::Data.gt(start_elegible_check))
-        .filter(lista_extra::Column::Data.lt(end_elegible_check))
-        .all(db)
-        .await?;
-
-    let extra_players = extra_players
-        .iter()
-        .map(|f| f.jogador_id)
-        .collect::<Vec<_>>();
 
-    let players = players
+    let escopo = match &jogo {
+        Some(jogo) => Escopo::Jogo(jogo),
+        None => Escopo::Semana(now),
+    };
+    let avaliacoes = eligibility::avaliar(&state, escopo).await?;
+    // Só vota quem jogou; admins podem votar mesmo assim
+    let impedimentos_voto = avaliacoes
         .iter()
-        .map(|f| f.email.to_owned())
-        .collect::<Option<Vec<_>>>()
+        .find(|a| a.jogador.id == user.id)
+        .map(|a| a.impedimentos_voto.iter().map(|i| i.to_string()).collect::<Vec<_>>())
         .unwrap_or_default();
-    let elegible_players = all_players
+    if !impedimentos_voto.is_empty() {
+        if user.admin {
+            info!(
+                "Admin {} voting despite: {:?}",
+                user.email, impedimentos_voto
+            );
+        } else {
+            info!("User {} cannot vote: {:?}", user.email, impedimentos_voto);
+            return Err(Error::Forbidden(format!(
+                "you cannot vote on this game. {}",
+                impedimentos_voto.join("; ")
+            )));
+        }
+    }
+    let elegible_players = avaliacoes
         .iter()
-        .filter(|x| players.contains(&x.email) || extra_players.contains(&x.id))
+        .filter(|a| a.elegivel())
+        .map(|a| &a.jogador)
         // Remove o jogador que está criando a votação
-        .filter(|x| x.email != identity.id().unwrap())
+        .filter(|x| x.email != user.email)
         .collect::<Vec<_>>();
 
-    info!(
-        "Elegible players({}): {:?}",
-        elegible_players.len(),
-        elegible_players.iter().map(|x| x.nome.to_owned()).collect::<Vec<String>>()
-    );
-
-    if elegible_players.len() < 5 {
+    let tamanho = jogo
+        .as_ref()
+        .map_or(*jogos::TAMANHO_CEDULA_SEMANAL, |j| j.tamanho_cedula);
+    if let Err(e) = jogos::validar_tamanho_cedula(tamanho) {
+        error!("Invalid ballot size: {}", e);
+        return Ok(create_bad_request("This game has an invalid ballot size"));
+    }
+    if elegible_players.len() < tamanho as usize {
         error!("Not enough players to create a ballot");
         return Ok(create_bad_request("Not enough players to create a ballot"));
     }
 
-    // Get 5 random players
-    let players = elegible_players
-        .choose_multiple(&mut rand::thread_rng(), 5)
-        .cloned()
-        .collect::<Vec<&jogador::Model>>();
+    // Get the players, favoring the ones with fewer ballots this week
+    let aparicoes = sorteio::aparicoes(db, event_id, jogo.as_ref().map(|j| j.id)).await?;
+    let players = state.sorteio.sortear(
+        &elegible_players,
+        &aparicoes,
+        tamanho as usize,
+        &mut StdRng::from_entropy(),
+    );
 
     let players_json = serde_json::json!(players.iter().map(|x| x.id).collect::<Vec<i32>>());
 
@@ -190,9 +290,11 @@ pub async fn voting_create(
         players: ActiveValue::Set(players_json),
         vote: ActiveValue::Set(serde_json::json!([])),
         date: ActiveValue::Set(chrono::Utc::now()),
-        voter: ActiveValue::Set(identity.id().unwrap().to_string()),
+        voter: ActiveValue::Set(user.email.clone()),
         fute_id: ActiveValue::Set(event_id),
-        state: ActiveValue::Set("open".to_string()), // TODO: Change to enum.
+        jogo_id: ActiveValue::Set(jogo.map(|j| j.id)),
+        tamanho: ActiveValue::Set(tamanho),
+        state: ActiveValue::Set(BallotState::Generated),
         ..Default::default()
     };
 
@@ -202,14 +304,13 @@ pub async fn voting_create(
         .body("Voting created"))
 }
 
-#[tracing::instrument(name = "Render Voting Form", skip(state, identity, path), fields(ballot_id = %path))]
+#[tracing::instrument(name = "Render Voting Form", skip(state, user, path), fields(ballot_id = %path))]
 #[get("/voting/{ballot_id}")]
 pub async fn vote(
     state: Data<AppState>,
     path: web::Path<u32>,
-    identity: Option<Identity>,
+    CurrentUser(user): CurrentUser,
 ) -> Result<impl Responder> {
-    let identity = identity.ok_or(anyhow!("Not logged in"))?;
     let ballot_id = path.into_inner();
     // Get players and add them to the context
     let mut players: Vec<list::Jogador> = vec![];
@@ -217,31 +318,47 @@ pub async fn vote(
     let db = &state.db;
     let ballot: Option<ballot::Model> = Ballot::find_by_id(ballot_id as i32).one(db).await?;
     if let Some(ballot) = ballot {
-        if ballot.voter != identity.id().unwrap() {
-            return Ok(
-                HttpResponse::Unauthorized().body("You are not allowed to vote on this ballot")
-            );
+        if ballot.voter != user.email {
+            return Err(Error::Forbidden(
+                "you are not allowed to vote on this ballot".to_string(),
+            ));
         }
         let mut context = tera::Context::new();
         context.insert("ballot_id", &ballot_id);
         let players_ids: Vec<i32> = serde_json::from_value(ballot.players)?;
-        for player_id in players_ids {
-            let player = Jogador::find_by_id(player_id)
+        // Numa cédula reaberta, o voto anterior já vem ordenado
+        let votados: Vec<i32> = serde_json::from_value(ballot.vote)?;
+        let mut voted = vec![];
+        for player_id in votados.iter().chain(players_ids.iter().filter(|x| !votados.contains(x))) {
+            let player = Jogador::find_by_id(*player_id)
                 .one(db)
                 .await?
                 .ok_or(anyhow!("Player not found"))?;
 
-            players.push(list::Jogador {
+            let player = list::Jogador {
                 id: player.id,
                 nome: player.nome,
                 apelido: player.apelido,
                 imagem: player.imagem,
-            });
+            };
+            if votados.contains(player_id) {
+                voted.push(player);
+            } else {
+                players.push(player);
+            }
         }
         context.insert("players", &players);
+        context.insert("voted", &voted);
         let semana = ref_point_from_id(ballot.fute_id);
         context.insert("semana", &semana.format("%d/%m/%Y").to_string());
         context.insert("week_id", &ballot.fute_id);
+        if let Some(jogo_id) = ballot.jogo_id {
+            let jogo = Jogo::find_by_id(jogo_id)
+                .one(db)
+                .await?
+                .ok_or(anyhow!("Game not found"))?;
+            context.insert("jogo", &jogo.nome);
+        }
 
         let page_content = TEMPLATES.render("voting.html", &context)?;
         Ok(HttpResponse::Ok().body(page_content))
@@ -255,93 +372,386 @@ struct CastVote {
     players: Vec<String>,
 }
 
-#[tracing::instrument(name = "Submit Vote", skip(state, path, cast_vote, identity), fields(ballot_id = %path))]
+/// Confere que o voto só ordena candidatos da cédula, sem repetir e sem
+/// passar do tamanho da cédula.
+fn validate_vote(ballot: &ballot::Model, players: &[String]) -> std::result::Result<Vec<i32>, String> {
+    let candidates: Vec<i32> = serde_json::from_value(ballot.players.clone())
+        .map_err(|_| "This ballot is corrupted".to_string())?;
+    if players.len() > ballot.tamanho as usize {
+        return Err(format!(
+            "Too many players in the vote: at most {} can be ranked",
+            ballot.tamanho
+        ));
+    }
+
+    let mut ranked = Vec::with_capacity(players.len());
+    for player in players {
+        let Ok(id) = player.trim().parse::<i32>() else {
+            return Err(format!("Invalid player id {:?}", player));
+        };
+        if !candidates.contains(&id) {
+            return Err(format!("Player {} is not a candidate on this ballot", id));
+        }
+        if ranked.contains(&id) {
+            return Err(format!("Player {} was ranked more than once", id));
+        }
+        ranked.push(id);
+    }
+    Ok(ranked)
+}
+
+/// Se ainda dá para votar (ou mudar o voto) nesta cédula.
+async fn voting_window_open(db: &DatabaseConnection, ballot: &ballot::Model) -> Result<bool> {
+    Ok(match ballot.jogo_id {
+        Some(jogo_id) => {
+            let jogo = Jogo::find_by_id(jogo_id)
+                .one(db)
+                .await?
+                .ok_or(anyhow!("Game not found"))?;
+            can_cast_vote_jogo(jogos::kickoff(&jogo))
+        }
+        None => can_cast_vote(ref_point_from_id(ballot.fute_id)),
+    })
+}
+
+#[tracing::instrument(name = "Submit Vote", skip(state, path, cast_vote, user), fields(ballot_id = %path))]
 #[post("/voting/{ballot_id}/")]
 pub async fn vote_submit(
     state: Data<AppState>,
     path: web::Path<u32>,
     cast_vote: web::Json<CastVote>,
-    identity: Option<Identity>,
+    CurrentUser(user): CurrentUser,
 ) -> Result<impl Responder> {
-    let ballot_id = path.into_inner();
-    let identity = identity.ok_or(anyhow!("Not logged in"))?;
+    let ballot_id = path.into_inner() as i32;
+    info!("Cast vote: {:?}", cast_vote);
+    match votar(&state.db, ballot_id, &user.email, &cast_vote.players).await? {
+        Ok(cast_vote) => {
+            tracing::info!("Votes: {:?}", cast_vote);
+            Ok(HttpResponse::Ok().body("Voto computado"))
+        }
+        Err(msg) => {
+            warn!("Rejected vote from {}: {}", user.email, msg);
+            Ok(create_bad_request(msg))
+        }
+    }
+}
 
-    let db = &state.db;
-    let ballot: ballot::Model = Ballot::find_by_id(ballot_id as i32)
+/// Grava o voto de `email` na cédula. `Ok(Err(motivo))` quando o voto é
+/// recusado, e o motivo vai de volta para o votante.
+async fn votar(
+    db: &DatabaseConnection,
+    ballot_id: i32,
+    email: &str,
+    players: &[String],
+) -> Result<std::result::Result<Vec<i32>, String>> {
+    let ballot: ballot::Model = Ballot::find_by_id(ballot_id)
         .one(db)
         .await?
         .ok_or(anyhow!("Ballot not found"))?;
-    if ballot.voter != identity.id().unwrap() {
-        warn!("User {} tried to vote on ballot {}, but it belongs to {}", identity.id().unwrap(), ballot_id, ballot.voter);
-        return Ok(HttpResponse::Unauthorized().body("You are not allowed to vote on this ballot"));
+    if ballot.voter != email {
+        warn!("User {} tried to vote on ballot {}, but it belongs to {}", email, ballot_id, ballot.voter);
+        return Err(Error::Forbidden(
+            "you are not allowed to vote on this ballot".to_string(),
+        ));
     }
-    if ballot.state != "open" {
-        return Ok(create_bad_request("Ballot is not open"));
+    if ballot.state != BallotState::Generated {
+        return Ok(Err("Ballot is not open".to_string()));
     }
-    let now = chrono::Utc::now();
-    let ballot_rt = ref_point_from_id(ballot.fute_id as i32);
-    if !can_cast_vote(ballot_rt) {
-        return Ok(create_bad_request("Voting is closed"));
+    if !voting_window_open(db, &ballot).await? {
+        return Ok(Err("Voting is closed".to_string()));
     }
 
     info!("Ballot: {:?}", ballot);
-    info!("Cast vote: {:?}", cast_vote);
-    let cast_vote = cast_vote
-        .players
-        .iter()
-        .map(|x| {
-            x.parse::<i32>()
-                .map_err(|_| Error::UnexpectedError(anyhow::Error::msg("Failed to parse vote")))
-        })
-        .collect::<Result<Vec<i32>>>()?;
+    let cast_vote = match validate_vote(&ballot, players) {
+        Ok(cast_vote) => cast_vote,
+        Err(msg) => return Ok(Err(msg)),
+    };
     info!("Cast vote: {:?}", cast_vote);
     let v = serde_json::to_value(cast_vote.clone())?;
-    ballot::ActiveModel {
-        vote: ActiveValue::Set(v),
-        state: ActiveValue::Set("closed".to_string()),
-        id: ActiveValue::Set(ballot_id as i32),
-        ..Default::default()
+    // Só grava se a apuração não tiver fechado a cédula no meio tempo
+    let votada = Ballot::update_many()
+        .col_expr(ballot::Column::Vote, Expr::value(v))
+        .col_expr(ballot::Column::State, Expr::value(BallotState::Submitted))
+        .filter(ballot::Column::Id.eq(ballot.id))
+        .filter(ballot::Column::State.eq(BallotState::Generated))
+        .exec(db)
+        .instrument(tracing::info_span!("Update ballot"))
+        .await?;
+    if votada.rows_affected == 0 {
+        return Ok(Err("Ballot is not open".to_string()));
     }
-    .update(db)
-    .instrument(tracing::info_span!("Update ballot"))
-    .await?;
     info!("Ballot updated");
-    let mut context = tera::Context::new();
-    context.insert("ballot_id", &ballot_id);
-    context.insert(
-        "votes",
-        &cast_vote
-            .iter()
-            .map(|x| x.to_string())
-            .collect::<Vec<String>>(),
-    );
-    tracing::info!("Votes: {:?}", cast_vote); 
-    Ok(HttpResponse::Ok().body("Voto computado"))
+    Ok(Ok(cast_vote))
+}
+
+/// Reabre a cédula já votada para o jogador mudar o voto, guardando o voto
+/// anterior. Depois da apuração a cédula não pode mais ser reaberta.
+#[tracing::instrument(name = "Reopen Ballot", skip(state, path, user), fields(ballot_id = %path))]
+#[post("/voting/{ballot_id}/reopen")]
+pub async fn vote_reopen(
+    state: Data<AppState>,
+    path: web::Path<u32>,
+    CurrentUser(user): CurrentUser,
+) -> Result<impl Responder> {
+    let ballot_id = path.into_inner() as i32;
+    if let Err(msg) = reabrir(&state.db, ballot_id, &user.email).await? {
+        return Ok(create_bad_request(msg));
+    }
+
+    Ok(HttpResponse::Ok()
+        .append_header(("HX-Redirect", format!("/voting/{}", ballot_id)))
+        .body("Ballot reopened"))
+}
+
+/// Volta a cédula de `email` para aberta. `Ok(Err(motivo))` quando ela não
+/// pode mais ser reaberta.
+async fn reabrir(
+    db: &DatabaseConnection,
+    ballot_id: i32,
+    email: &str,
+) -> Result<std::result::Result<(), &'static str>> {
+    let ballot: ballot::Model = Ballot::find_by_id(ballot_id)
+        .one(db)
+        .await?
+        .ok_or(anyhow!("Ballot not found"))?;
+    if ballot.voter != email {
+        warn!("User {} tried to reopen ballot {}, but it belongs to {}", email, ballot_id, ballot.voter);
+        return Err(Error::Forbidden(
+            "you are not allowed to vote on this ballot".to_string(),
+        ));
+    }
+    match ballot.state {
+        BallotState::Generated => {}
+        BallotState::Computed => return Ok(Err("This ballot was already counted")),
+        BallotState::Submitted => {
+            if ja_apurada(db, &ballot).await? {
+                return Ok(Err("This ballot was already counted"));
+            }
+            if !voting_window_open(db, &ballot).await? {
+                return Ok(Err("Voting is closed"));
+            }
+            let txn = db.begin().await?;
+            revisao_cedula::ActiveModel {
+                ballot_id: ActiveValue::Set(ballot.id),
+                vote: ActiveValue::Set(ballot.vote.clone()),
+                criado_em: ActiveValue::Set(chrono::Utc::now()),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            // Só reabre se a apuração não tiver fechado a cédula no meio tempo
+            let reaberta = Ballot::update_many()
+                .col_expr(ballot::Column::State, Expr::value(BallotState::Generated))
+                .filter(ballot::Column::Id.eq(ballot.id))
+                .filter(ballot::Column::State.eq(BallotState::Submitted))
+                .exec(&txn)
+                .await?;
+            if reaberta.rows_affected == 0 {
+                txn.rollback().await?;
+                return Ok(Err("This ballot was already counted"));
+            }
+            txn.commit().await?;
+            info!("User {} reopened ballot {}", email, ballot_id);
+        }
+    }
+    Ok(Ok(()))
 }
 
-#[tracing::instrument(name = "Render Voting Success", skip(state, path, identity), fields(ballot_id = %path))]
+/// Se a semana ou o jogo da cédula já tem apuração, mesmo que a cédula
+/// tenha escapado de ser fechada.
+async fn ja_apurada(db: &DatabaseConnection, ballot: &ballot::Model) -> Result<bool> {
+    Ok(match ballot.jogo_id {
+        Some(jogo_id) => ApuracaoJogo::find()
+            .filter(apuracao_jogo::Column::JogoId.eq(jogo_id))
+            .one(db)
+            .await?
+            .is_some(),
+        None => Apuracao::find()
+            .filter(apuracao::Column::WeekId.eq(ballot.fute_id))
+            .one(db)
+            .await?
+            .is_some(),
+    })
+}
+
+#[tracing::instrument(name = "Render Voting Success", skip(state, path, user), fields(ballot_id = %path))]
 #[get("/voting/{ballot_id}/success")]
 pub async fn vote_success(
     state: Data<AppState>,
     path: web::Path<u32>,
-    identity: Option<Identity>,
+    CurrentUser(user): CurrentUser,
 ) -> Result<impl Responder> {
-    let identity = identity.ok_or(anyhow!("Not logged in"))?;
     let ballot_id = path.into_inner();
     let db = &state.db;
     let ballot: Option<ballot::Model> = Ballot::find_by_id(ballot_id as i32).one(db).await?;
     if let Some(ballot) = ballot {
-        if ballot.voter != identity.id().unwrap() {
-            return Ok(
-                HttpResponse::Unauthorized().body("You are not allowed to vote on this ballot")
-            );
+        if ballot.voter != user.email {
+            return Err(Error::Forbidden(
+                "you are not allowed to vote on this ballot".to_string(),
+            ));
         }
         let mut context = tera::Context::new();
         context.insert("ballot_id", &ballot_id);
-        let votes: Vec<i32> = serde_json::from_value(ballot.vote)?;
+        let pode_alterar = ballot.state == BallotState::Submitted
+            && voting_window_open(db, &ballot).await?;
+        context.insert("pode_alterar", &pode_alterar);
         let page_content = TEMPLATES.render("voting_success.html", &context)?;
         Ok(HttpResponse::Ok().body(page_content))
     } else {
         Ok(HttpResponse::NotFound().body("Ballot not found"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::entities::sea_orm_active_enums::ApuracaoState;
+    use chrono::{Duration, Utc};
+    use sea_orm::IntoActiveModel;
+
+    fn cedula() -> ballot::Model {
+        ballot::Model {
+            state: BallotState::Generated,
+            ..fixtures::cedula(&[10, 20, 30, 40, 50], &[])
+        }
+    }
+
+    fn voto(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn aceita_voto_valido() {
+        assert_eq!(validate_vote(&cedula(), &voto(&["30", "10"])), Ok(vec![30, 10]));
+        assert_eq!(validate_vote(&cedula(), &voto(&[])), Ok(vec![]));
+    }
+
+    #[test]
+    fn rejeita_jogador_de_fora_da_cedula() {
+        let err = validate_vote(&cedula(), &voto(&["10", "99"])).unwrap_err();
+        assert!(err.contains("99"), "{err}");
+    }
+
+    #[test]
+    fn rejeita_jogador_repetido() {
+        assert!(validate_vote(&cedula(), &voto(&["10", "10"])).is_err());
+        // Espaços não escondem a repetição
+        assert!(validate_vote(&cedula(), &voto(&["10", " 10"])).is_err());
+    }
+
+    #[test]
+    fn rejeita_votos_demais() {
+        let ids = ["10", "20", "30", "40", "50", "10"];
+        assert!(validate_vote(&cedula(), &voto(&ids)).is_err());
+        let mut pequena = cedula();
+        pequena.tamanho = 2;
+        assert!(validate_vote(&pequena, &voto(&["10", "20", "30"])).is_err());
+    }
+
+    #[test]
+    fn rejeita_ids_malformados() {
+        for id in ["", "abc", "1.5", "99999999999", "10; DROP TABLE ballot", "-10"] {
+            assert!(validate_vote(&cedula(), &voto(&[id])).is_err(), "{id}");
+        }
+    }
+
+    const VOTANTE: &str = "voter@inf.ufrgs.br";
+
+    /// Cédula aberta de um jogo que acabou de começar, com a votação aberta.
+    async fn cedula_do_jogo(db: &DatabaseConnection) -> ballot::Model {
+        let inicio = (Utc::now() - Duration::minutes(1))
+            .with_timezone(&timings::timezone())
+            .naive_local();
+        let mut jogo = fixtures::cria_jogo(db, 10).await.into_active_model();
+        jogo.data = ActiveValue::Set(inicio.date());
+        jogo.hora = ActiveValue::Set(inicio.time());
+        let jogo = jogo.update(db).await.unwrap();
+        let cedula = ballot::Model {
+            jogo_id: Some(jogo.id),
+            fute_id: jogos::week_id(&jogo),
+            ..cedula()
+        };
+        fixtures::cria_cedula(db, cedula).await
+    }
+
+    async fn revisoes(db: &DatabaseConnection, ballot_id: i32) -> Vec<serde_json::Value> {
+        revisao_cedula::Entity::find()
+            .filter(revisao_cedula::Column::BallotId.eq(ballot_id))
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.vote)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reabrir_guarda_o_voto_anterior() {
+        let db = fixtures::banco().await;
+        let cedula = cedula_do_jogo(&db).await;
+        votar(&db, cedula.id, VOTANTE, &voto(&["20", "10"])).await.unwrap().unwrap();
+
+        reabrir(&db, cedula.id, VOTANTE).await.unwrap().unwrap();
+
+        let reaberta = Ballot::find_by_id(cedula.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(reaberta.state, BallotState::Generated);
+        assert_eq!(revisoes(&db, cedula.id).await, vec![serde_json::json!([20, 10])]);
+    }
+
+    #[tokio::test]
+    async fn nao_reabre_cedula_apurada() {
+        let db = fixtures::banco().await;
+        let cedula = cedula_do_jogo(&db).await;
+        let mut computada = cedula.clone().into_active_model();
+        computada.state = ActiveValue::Set(BallotState::Computed);
+        computada.update(&db).await.unwrap();
+
+        assert!(reabrir(&db, cedula.id, VOTANTE).await.unwrap().is_err());
+
+        // Votada, mas o jogo já foi apurado
+        let cedula = cedula_do_jogo(&db).await;
+        votar(&db, cedula.id, VOTANTE, &voto(&["10"])).await.unwrap().unwrap();
+        apuracao_jogo::ActiveModel {
+            jogo_id: ActiveValue::Set(cedula.jogo_id.unwrap()),
+            state: ActiveValue::Set(ApuracaoState::Complete),
+            results: ActiveValue::Set(serde_json::json!({})),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        assert!(reabrir(&db, cedula.id, VOTANTE).await.unwrap().is_err());
+        assert!(revisoes(&db, cedula.id).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn nao_reabre_cedula_de_outro() {
+        let db = fixtures::banco().await;
+        let cedula = cedula_do_jogo(&db).await;
+        votar(&db, cedula.id, VOTANTE, &voto(&["10"])).await.unwrap().unwrap();
+
+        let err = reabrir(&db, cedula.id, "outro@inf.ufrgs.br").await.unwrap_err();
+
+        assert!(matches!(err, Error::Forbidden(_)), "{err:?}");
+        assert!(revisoes(&db, cedula.id).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reabrir_e_votar_de_novo() {
+        let db = fixtures::banco().await;
+        let cedula = cedula_do_jogo(&db).await;
+        votar(&db, cedula.id, VOTANTE, &voto(&["20"])).await.unwrap().unwrap();
+        assert!(votar(&db, cedula.id, VOTANTE, &voto(&["30"])).await.unwrap().is_err());
+
+        reabrir(&db, cedula.id, VOTANTE).await.unwrap().unwrap();
+        let novo = votar(&db, cedula.id, VOTANTE, &voto(&["30", "10"])).await.unwrap();
+
+        assert_eq!(novo, Ok(vec![30, 10]));
+        let votada = Ballot::find_by_id(cedula.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(votada.state, BallotState::Submitted);
+        assert_eq!(votada.vote, serde_json::json!([30, 10]));
+        assert_eq!(revisoes(&db, cedula.id).await, vec![serde_json::json!([20])]);
+    }
+}
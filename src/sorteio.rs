@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+
+use crate::entities::{prelude::*, *};
+use crate::error::Result;
+
+/// Como escolher os candidatos de uma cédula.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sorteio {
+    /// Todos os elegíveis com a mesma chance.
+    Uniforme,
+    /// Favorece quem apareceu em menos cédulas na semana, para que todos
+    /// recebam um número parecido de votos.
+    Balanceado,
+}
+
+/// Diferença de aparições a partir da qual o balanceado não diminui mais a
+/// chance de um jogador.
+const DIFERENCA_MAXIMA: usize = 16;
+
+impl Sorteio {
+    /// Lê `SORTEIO` (`balanceado`, o padrão, ou `uniforme`).
+    pub fn from_env() -> Self {
+        let sorteio = std::env::var("SORTEIO").unwrap_or_else(|_| "balanceado".to_string());
+        match sorteio.as_str() {
+            "balanceado" => Sorteio::Balanceado,
+            "uniforme" => Sorteio::Uniforme,
+            other => panic!("Unknown SORTEIO {other:?}, use \"balanceado\" or \"uniforme\""),
+        }
+    }
+
+    /// Sorteia `n` candidatos distintos. `aparicoes` conta em quantas cédulas
+    /// cada jogador já apareceu; quem não está no mapa nunca apareceu.
+    pub fn sortear<'a, R: Rng + ?Sized>(
+        &self,
+        candidatos: &[&'a jogador::Model],
+        aparicoes: &HashMap<i32, usize>,
+        n: usize,
+        rng: &mut R,
+    ) -> Vec<&'a jogador::Model> {
+        match self {
+            Sorteio::Uniforme => candidatos.choose_multiple(rng, n).cloned().collect(),
+            Sorteio::Balanceado => {
+                let vezes = |jogador: &jogador::Model| {
+                    aparicoes.get(&jogador.id).copied().unwrap_or(0)
+                };
+                let menor = candidatos.iter().map(|j| vezes(j)).min().unwrap_or(0);
+                // Cada aparição a mais que o menos votado divide a chance por 4,
+                // até um limite, para o peso não chegar a zero
+                let peso = |jogador: &&jogador::Model| {
+                    let diferenca = (vezes(jogador) - menor).min(DIFERENCA_MAXIMA);
+                    0.25f64.powi(diferenca as i32)
+                };
+                match candidatos.choose_multiple_weighted(rng, n, peso) {
+                    Ok(escolhidos) => escolhidos.cloned().collect(),
+                    Err(e) => {
+                        tracing::warn!("Invalid draw weights, drawing uniformly: {e}");
+                        candidatos.choose_multiple(rng, n).cloned().collect()
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Em quantas cédulas da semana (ou do jogo) cada jogador apareceu.
+pub async fn aparicoes(
+    db: &DatabaseConnection,
+    fute_id: i32,
+    jogo_id: Option<i32>,
+) -> Result<HashMap<i32, usize>> {
+    let ballots = Ballot::find()
+        .filter(ballot::Column::FuteId.eq(fute_id))
+        .filter(match jogo_id {
+            Some(jogo_id) => ballot::Column::JogoId.eq(jogo_id),
+            None => ballot::Column::JogoId.is_null(),
+        })
+        .all(db)
+        .await?;
+
+    let mut aparicoes = HashMap::new();
+    for ballot in ballots {
+        let players: Vec<i32> = serde_json::from_value(ballot.players)?;
+        for player in players {
+            *aparicoes.entry(player).or_insert(0) += 1;
+        }
+    }
+    Ok(aparicoes)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::fixtures::jogadores;
+
+    /// Cria `cedulas` cédulas seguidas e devolve quantas vezes cada um apareceu.
+    fn simula(sorteio: Sorteio, cedulas: usize, seed: u64) -> HashMap<i32, usize> {
+        let todos = jogadores(20);
+        let candidatos = todos.iter().collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut aparicoes = HashMap::new();
+        for _ in 0..cedulas {
+            for jogador in sorteio.sortear(&candidatos, &aparicoes, 5, &mut rng) {
+                *aparicoes.entry(jogador.id).or_insert(0) += 1;
+            }
+        }
+        aparicoes
+    }
+
+    fn amplitude(aparicoes: &HashMap<i32, usize>) -> usize {
+        let (min, max) = (1..=20)
+            .map(|id| aparicoes.get(&id).copied().unwrap_or(0))
+            .fold((usize::MAX, 0), |(min, max), x| (min.min(x), max.max(x)));
+        max - min
+    }
+
+    #[test]
+    fn sorteia_candidatos_distintos() {
+        let todos = jogadores(8);
+        let candidatos = todos.iter().collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(1);
+        for sorteio in [Sorteio::Uniforme, Sorteio::Balanceado] {
+            let mut ids = sorteio
+                .sortear(&candidatos, &HashMap::new(), 5, &mut rng)
+                .iter()
+                .map(|j| j.id)
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids.dedup();
+            assert_eq!(ids.len(), 5);
+        }
+    }
+
+    #[test]
+    fn mesma_semente_mesmo_sorteio() {
+        assert_eq!(simula(Sorteio::Balanceado, 30, 7), simula(Sorteio::Balanceado, 30, 7));
+        assert_eq!(simula(Sorteio::Uniforme, 30, 7), simula(Sorteio::Uniforme, 30, 7));
+    }
+
+    #[test]
+    fn balanceado_equilibra_as_aparicoes() {
+        let balanceado = simula(Sorteio::Balanceado, 40, 42);
+        let uniforme = simula(Sorteio::Uniforme, 40, 42);
+        // 40 cédulas de 5 entre 20 jogadores: 10 aparições cada
+        assert!(amplitude(&balanceado) <= 2, "{balanceado:?}");
+        assert!(amplitude(&balanceado) < amplitude(&uniforme));
+    }
+
+    #[test]
+    fn diferenca_enorme_de_aparicoes_nao_derruba_o_sorteio() {
+        let todos = jogadores(6);
+        let candidatos = todos.iter().collect::<Vec<_>>();
+        let aparicoes = HashMap::from([(1, 0), (2, 5_000), (3, 5_000), (4, usize::MAX / 2)]);
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let escolhidos = Sorteio::Balanceado.sortear(&candidatos, &aparicoes, 5, &mut rng);
+
+        let mut ids = escolhidos.iter().map(|j| j.id).collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+    }
+}
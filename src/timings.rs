@@ -1,28 +1,240 @@
 use chrono::prelude::*;
-use chrono::Duration;
+use chrono::{Duration, DurationRound};
+use chrono_tz::Tz;
+use lazy_static::lazy_static;
+use serde::Deserialize;
 
 pub const WEEK_IN_SECONDS: i64 = 604800;
 
-pub fn get_first_ref_point() -> DateTime<Utc> {
-    Local
-        .with_ymd_and_hms(2023, 6, 4, 19, 0, 0)
-        .unwrap()
-        .with_timezone(&Utc)
+/// Configuração do calendário a partir de uma semana.
+///
+/// Cada período vale do seu primeiro ponto de referência até o primeiro ponto
+/// de referência do período seguinte, então mudar o dia do jogo é só adicionar
+/// um período novo e os `week_id` antigos continuam nas mesmas datas.
+///
+/// As semanas são contadas no horário local do fuso do calendário, então o
+/// ponto de referência fica sempre no mesmo horário de parede, mesmo quando
+/// a semana tem 167 ou 169 horas por causa do horário de verão.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SchedulePeriod {
+    /// Primeiro `week_id` em que esta configuração vale
+    pub first_week_id: i32,
+    /// Horário do jogo da semana `first_week_id`, no fuso do calendário
+    pub ref_point: NaiveDateTime,
+    /// Por quanto tempo a votação fica aberta depois do jogo
+    #[serde(default = "default_voting_window")]
+    pub voting_window_minutes: i64,
+    /// Antes do fim da votação, por quanto tempo só é possível votar em cédulas já criadas
+    #[serde(default = "default_create_cutoff")]
+    pub create_cutoff_minutes: i64,
+    /// Quanto tempo depois do fim da votação o resultado é publicado
+    #[serde(default = "default_publish_delay")]
+    pub publish_delay_minutes: i64,
 }
 
-pub fn get_ref_point_of(date: DateTime<Utc>) -> DateTime<Utc> {
-    let past_reset = get_first_ref_point();
+fn default_voting_window() -> i64 {
+    WEEK_IN_SECONDS / 60
+}
+
+fn default_create_cutoff() -> i64 {
+    3
+}
+
+fn default_publish_delay() -> i64 {
+    90
+}
+
+impl SchedulePeriod {
+    /// Horário local do jogo na semana `id`
+    fn local_ref_point(&self, id: i32) -> NaiveDateTime {
+        self.ref_point + Duration::days(7 * (id - self.first_week_id) as i64)
+    }
+
+    fn voting_window(&self) -> Duration {
+        Duration::minutes(self.voting_window_minutes)
+    }
+
+    fn create_cutoff(&self) -> Duration {
+        Duration::minutes(self.create_cutoff_minutes)
+    }
 
-    //get Duration since reference reset
-    let d = date - past_reset;
+    fn publish_delay(&self) -> Duration {
+        Duration::minutes(self.publish_delay_minutes)
+    }
+}
 
-    // divide by a week, and get the remainder
-    let rem = d.num_seconds() % WEEK_IN_SECONDS;
+fn default_timezone() -> Tz {
+    chrono_tz::America::Sao_Paulo
+}
 
-    //subtract the remainder from current time
+#[derive(Debug, Clone, Deserialize)]
+pub struct Schedule {
+    /// Fuso horário em que os jogos acontecem
+    #[serde(default = "default_timezone")]
+    timezone: Tz,
+    periods: Vec<SchedulePeriod>,
+}
 
-    //last reset / datetime
-    date - Duration::seconds(rem)
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            timezone: default_timezone(),
+            periods: vec![SchedulePeriod {
+                first_week_id: 0,
+                ref_point: NaiveDate::from_ymd_opt(2023, 6, 4)
+                    .unwrap()
+                    .and_hms_opt(19, 0, 0)
+                    .unwrap(),
+                voting_window_minutes: default_voting_window(),
+                create_cutoff_minutes: default_create_cutoff(),
+                publish_delay_minutes: default_publish_delay(),
+            }],
+        }
+    }
+}
+
+impl Schedule {
+    /// Lê o calendário do arquivo JSON em `SCHEDULE_FILE`, ou usa o padrão
+    /// (domingos às 19h em São Paulo desde 04/06/2023). Por exemplo, para passar
+    /// o jogo para segunda às 20h a partir da semana 80:
+    ///
+    /// ```json
+    /// {"timezone": "America/Sao_Paulo", "periods": [
+    ///     {"first_week_id": 0, "ref_point": "2023-06-04T19:00:00"},
+    ///     {"first_week_id": 80, "ref_point": "2024-12-16T20:00:00", "publish_delay_minutes": 60}
+    /// ]}
+    /// ```
+    pub fn from_env() -> anyhow::Result<Self> {
+        let schedule = match std::env::var("SCHEDULE_FILE") {
+            Ok(path) => {
+                let content = std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+                serde_json::from_str(&content)?
+            }
+            Err(_) => Schedule::default(),
+        };
+        schedule.validate()?;
+        Ok(schedule)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.periods.is_empty() {
+            anyhow::bail!("The schedule needs at least one period");
+        }
+        for period in &self.periods {
+            if period.voting_window_minutes <= 0
+                || period.voting_window_minutes * 60 > WEEK_IN_SECONDS
+            {
+                anyhow::bail!("The voting window must be between 1 minute and 1 week");
+            }
+            if period.create_cutoff_minutes < 0
+                || period.create_cutoff_minutes >= period.voting_window_minutes
+            {
+                anyhow::bail!("The ballot creation cutoff must be shorter than the voting window");
+            }
+            if period.publish_delay_minutes < 0 {
+                anyhow::bail!("The publish delay can't be negative");
+            }
+        }
+        for (prev, next) in self.periods.iter().zip(self.periods.iter().skip(1)) {
+            if next.first_week_id <= prev.first_week_id {
+                anyhow::bail!("Schedule periods must be sorted by first_week_id");
+            }
+            // A última semana do período anterior tem que terminar depois de começar
+            // e não pode durar mais do que duas semanas
+            let last_prev_week = self.ref_point_in(prev, next.first_week_id - 1);
+            let first_next_week = self.first_ref_point(next);
+            if first_next_week <= last_prev_week
+                || first_next_week > last_prev_week + Duration::weeks(2)
+            {
+                anyhow::bail!(
+                    "The period starting at week {} doesn't follow the previous one",
+                    next.first_week_id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Converte um horário local para UTC. Se o horário não existe (pulado pelo
+    /// horário de verão) usa o primeiro instante depois do pulo, se existe duas
+    /// vezes usa o primeiro.
+    fn resolve_local(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let mut candidate = local;
+        loop {
+            if let Some(date) = self.timezone.from_local_datetime(&candidate).earliest() {
+                return date.with_timezone(&Utc);
+            }
+            // Nenhum fuso pula mais do que algumas horas, então isso termina rápido
+            candidate = candidate.duration_trunc(Duration::minutes(15)).unwrap_or(candidate)
+                + Duration::minutes(15);
+        }
+    }
+
+    fn ref_point_in(&self, period: &SchedulePeriod, id: i32) -> DateTime<Utc> {
+        self.resolve_local(period.local_ref_point(id))
+    }
+
+    fn first_ref_point(&self, period: &SchedulePeriod) -> DateTime<Utc> {
+        self.ref_point_in(period, period.first_week_id)
+    }
+
+    /// Índice do período que vale para a data, os anteriores ao primeiro usam o primeiro.
+    fn period_index_of(&self, date: DateTime<Utc>) -> usize {
+        self.periods
+            .iter()
+            .rposition(|p| self.first_ref_point(p) <= date)
+            .unwrap_or(0)
+    }
+
+    fn period_of(&self, date: DateTime<Utc>) -> &SchedulePeriod {
+        &self.periods[self.period_index_of(date)]
+    }
+
+    fn period_of_id(&self, id: i32) -> &SchedulePeriod {
+        self.periods
+            .iter()
+            .rev()
+            .find(|p| p.first_week_id <= id)
+            .unwrap_or(&self.periods[0])
+    }
+
+    pub fn ref_point_id(&self, date: DateTime<Utc>) -> i32 {
+        let index = self.period_index_of(date);
+        let period = &self.periods[index];
+
+        // Conta os dias no calendário local, assim semanas com mudança de
+        // horário de verão não deslocam o ponto de referência
+        let local = date.with_timezone(&self.timezone).naive_local();
+        let days = (local - period.ref_point).num_days();
+        let mut id = period.first_week_id + days.div_euclid(7) as i32;
+        // Corrige a estimativa perto da virada da semana
+        while self.ref_point_in(period, id) > date {
+            id -= 1;
+        }
+        while self.ref_point_in(period, id + 1) <= date {
+            id += 1;
+        }
+
+        // Se o jogo passou para mais tarde, a última semana do período é mais longa
+        match self.periods.get(index + 1) {
+            Some(next) => id.min(next.first_week_id - 1),
+            None => id,
+        }
+    }
+
+    pub fn ref_point_from_id(&self, id: i32) -> DateTime<Utc> {
+        self.ref_point_in(self.period_of_id(id), id)
+    }
+}
+
+lazy_static! {
+    pub static ref SCHEDULE: Schedule =
+        Schedule::from_env().expect("Invalid schedule configuration");
+}
+
+pub fn get_ref_point_of(date: DateTime<Utc>) -> DateTime<Utc> {
+    ref_point_from_id(ref_point_id(date))
 }
 
 pub fn get_last_ref_point() -> DateTime<Utc> {
@@ -30,11 +242,6 @@ pub fn get_last_ref_point() -> DateTime<Utc> {
     get_ref_point_of(now)
 }
 
-pub fn get_start_elegible_check(date: DateTime<Utc>) -> DateTime<Utc> {
-    let last_reset = get_ref_point_of(date);
-    last_reset - Duration::weeks(4)
-}
-
 pub fn get_end_elegible_check(date: DateTime<Utc>) -> DateTime<Utc> {
     get_ref_point_of(date)
 }
@@ -44,14 +251,15 @@ pub fn get_start_voting(date: DateTime<Utc>) -> DateTime<Utc> {
 }
 
 pub fn get_end_create_ballot(date: DateTime<Utc>) -> DateTime<Utc> {
-    let last_reset = get_ref_point_of(date);
-    // In the last 3 minutes of the voting we can only cast votes already created
-    last_reset + Duration::weeks(1) - Duration::minutes(3)
+    let period = SCHEDULE.period_of(date);
+    // In the last minutes of the voting we can only cast votes already created
+    get_end_voting(date) - period.create_cutoff()
 }
 
 pub fn get_end_voting(date: DateTime<Utc>) -> DateTime<Utc> {
     let last_reset = get_ref_point_of(date);
-    last_reset + Duration::weeks(1)
+    let period = SCHEDULE.period_of(date);
+    last_reset + period.voting_window()
 }
 
 pub fn can_create_ballot(date: DateTime<Utc>) -> bool {
@@ -69,23 +277,208 @@ pub fn can_cast_vote(rf: DateTime<Utc>) -> bool {
 
 pub fn publish_time(date: DateTime<Utc>) -> DateTime<Utc> {
     let end = get_end_voting(date);
-    end + Duration::minutes(90)
+    let period = SCHEDULE.period_of(date);
+    end + period.publish_delay()
+}
+
+pub fn ref_point_id(date: DateTime<Utc>) -> i32 {
+    SCHEDULE.ref_point_id(date)
+}
+
+pub fn ref_point_from_id(id: i32) -> DateTime<Utc> {
+    SCHEDULE.ref_point_from_id(id)
+}
+
+/// Fuso horário configurado para o calendário dos jogos.
+pub fn timezone() -> Tz {
+    SCHEDULE.timezone
+}
+
+/// Converte um horário local do calendário para UTC, veja [`Schedule::resolve_local`].
+pub fn from_local(local: NaiveDateTime) -> DateTime<Utc> {
+    SCHEDULE.resolve_local(local)
 }
 
-pub fn publish_results(date: DateTime<Utc>) -> bool {
-    let start_publish = publish_time(date);
+// Cada jogo tem sua própria votação, que começa no horário do jogo e usa a
+// duração e os prazos do período do calendário em que o jogo acontece.
+
+pub fn get_end_voting_jogo(kickoff: DateTime<Utc>) -> DateTime<Utc> {
+    kickoff + SCHEDULE.period_of(kickoff).voting_window()
+}
+
+pub fn get_end_create_ballot_jogo(kickoff: DateTime<Utc>) -> DateTime<Utc> {
+    get_end_voting_jogo(kickoff) - SCHEDULE.period_of(kickoff).create_cutoff()
+}
+
+pub fn can_create_ballot_jogo(kickoff: DateTime<Utc>, date: DateTime<Utc>) -> bool {
+    date >= kickoff && date < get_end_create_ballot_jogo(kickoff)
+}
+
+pub fn can_cast_vote_jogo(kickoff: DateTime<Utc>) -> bool {
     let now = Utc::now();
-    now >= start_publish
+    now >= kickoff && now < get_end_voting_jogo(kickoff)
 }
 
-pub fn ref_point_id(date: DateTime<Utc>) -> i32 {
-    let last_reset = get_ref_point_of(date);
-    let first_reset = get_first_ref_point();
-    let weeks = (last_reset - first_reset).num_weeks();
-    weeks as i32
+pub fn publish_time_jogo(kickoff: DateTime<Utc>) -> DateTime<Utc> {
+    get_end_voting_jogo(kickoff) + SCHEDULE.period_of(kickoff).publish_delay()
 }
 
-pub fn ref_point_from_id(id: i32) -> DateTime<Utc> {
-    let first_reset = get_first_ref_point();
-    first_reset + Duration::weeks(id as i64)
+pub fn publish_results_jogo(kickoff: DateTime<Utc>) -> bool {
+    Utc::now() >= publish_time_jogo(kickoff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn period(first_week_id: i32, ref_point: &str) -> SchedulePeriod {
+        SchedulePeriod {
+            first_week_id,
+            ref_point: NaiveDateTime::parse_from_str(ref_point, "%Y-%m-%dT%H:%M").unwrap(),
+            voting_window_minutes: default_voting_window(),
+            create_cutoff_minutes: default_create_cutoff(),
+            publish_delay_minutes: default_publish_delay(),
+        }
+    }
+
+    fn schedules() -> Vec<Schedule> {
+        vec![
+            // São Paulo teve horário de verão até 2019, as semanas negativas passam por ele
+            Schedule::default(),
+            // Horário de verão mudando à meia-noite, o jogo cai no horário pulado
+            Schedule {
+                timezone: chrono_tz::America::Sao_Paulo,
+                periods: vec![period(0, "2018-11-04T00:00")],
+            },
+            Schedule {
+                timezone: chrono_tz::America::New_York,
+                periods: vec![period(0, "2023-06-04T19:00")],
+            },
+            Schedule {
+                timezone: chrono_tz::Europe::London,
+                periods: vec![period(0, "2023-06-04T01:30")],
+            },
+            // Jogo passa de domingo 19h para segunda 20h, e depois para sábado 10h
+            Schedule {
+                timezone: chrono_tz::America::Sao_Paulo,
+                periods: vec![
+                    period(0, "2023-06-04T19:00"),
+                    period(80, "2024-12-16T20:00"),
+                    period(100, "2025-05-03T10:00"),
+                ],
+            },
+        ]
+    }
+
+    fn any_schedule() -> impl Strategy<Value = Schedule> {
+        (0..schedules().len()).prop_map(|i| schedules().swap_remove(i))
+    }
+
+    /// Datas entre 2010 e 2035
+    fn any_date() -> impl Strategy<Value = DateTime<Utc>> {
+        (1_262_304_000i64..2_051_222_400i64)
+            .prop_map(|ts| DateTime::from_timestamp(ts, 0).unwrap())
+    }
+
+    #[test]
+    fn calendarios_sao_validos() {
+        for schedule in schedules() {
+            schedule.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn calendario_padrao_mantem_ids_historicos() {
+        let schedule = Schedule::default();
+        let first = Utc.with_ymd_and_hms(2023, 6, 4, 22, 0, 0).unwrap();
+        assert_eq!(schedule.ref_point_from_id(0), first);
+        assert_eq!(schedule.ref_point_id(first), 0);
+        assert_eq!(schedule.ref_point_id(first - Duration::seconds(1)), -1);
+        assert_eq!(
+            schedule.ref_point_from_id(76),
+            Utc.with_ymd_and_hms(2024, 11, 17, 22, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn referencia_mantem_horario_local_no_horario_de_verao() {
+        let schedule = Schedule {
+            timezone: chrono_tz::America::New_York,
+            periods: vec![period(0, "2023-06-04T19:00")],
+        };
+        // Antes e depois do fim do horário de verão (05/11/2023)
+        let summer = schedule.ref_point_from_id(21);
+        let winter = schedule.ref_point_from_id(22);
+        assert_eq!(summer, Utc.with_ymd_and_hms(2023, 10, 29, 23, 0, 0).unwrap());
+        assert_eq!(winter, Utc.with_ymd_and_hms(2023, 11, 6, 0, 0, 0).unwrap());
+        assert_eq!(winter - summer, Duration::hours(7 * 24 + 1));
+    }
+
+    #[test]
+    fn referencia_na_lacuna_do_horario_de_verao_vai_para_depois() {
+        let schedule = Schedule {
+            timezone: chrono_tz::America::Sao_Paulo,
+            periods: vec![period(0, "2018-10-28T00:00")],
+        };
+        // 04/11/2018 00:00 não existe em São Paulo, o relógio pulou para 01:00
+        let gap = schedule.ref_point_from_id(1);
+        let local = gap.with_timezone(&schedule.timezone);
+        let after_gap = NaiveDate::from_ymd_opt(2018, 11, 4)
+            .unwrap()
+            .and_hms_opt(1, 0, 0)
+            .unwrap();
+        assert_eq!(local.naive_local(), after_gap);
+        assert_eq!(schedule.ref_point_id(gap), 1);
+        assert_eq!(schedule.ref_point_id(gap - Duration::seconds(1)), 0);
+    }
+
+    #[test]
+    fn semana_de_transicao_mais_longa() {
+        let schedule = &schedules()[4];
+        let last_old_week = schedule.ref_point_from_id(79);
+        let first_new_week = schedule.ref_point_from_id(80);
+        assert_eq!(first_new_week - last_old_week, Duration::hours(8 * 24 + 1));
+        assert_eq!(schedule.ref_point_id(first_new_week - Duration::seconds(1)), 79);
+    }
+
+    #[test]
+    fn transicao_invalida_e_rejeitada() {
+        let schedule = Schedule {
+            timezone: chrono_tz::America::Sao_Paulo,
+            periods: vec![period(0, "2023-06-04T19:00"), period(80, "2024-12-01T19:00")],
+        };
+        assert!(schedule.validate().is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn id_ida_e_volta(schedule in any_schedule(), id in -700i32..1000) {
+            let ref_point = schedule.ref_point_from_id(id);
+            prop_assert_eq!(schedule.ref_point_id(ref_point), id);
+        }
+
+        #[test]
+        fn data_fica_dentro_da_sua_semana(schedule in any_schedule(), date in any_date()) {
+            let id = schedule.ref_point_id(date);
+            prop_assert!(schedule.ref_point_from_id(id) <= date);
+            prop_assert!(date < schedule.ref_point_from_id(id + 1));
+        }
+
+        #[test]
+        fn ids_sao_crescentes(schedule in any_schedule(), a in any_date(), b in any_date()) {
+            let (a, b) = if a <= b { (a, b) } else { (b, a) };
+            prop_assert!(schedule.ref_point_id(a) <= schedule.ref_point_id(b));
+        }
+
+        #[test]
+        fn referencia_mantem_horario_local(schedule in any_schedule(), id in -700i32..1000) {
+            let period = schedule.period_of_id(id);
+            let expected = period.local_ref_point(id);
+            // Só vale quando o horário existe naquele dia
+            prop_assume!(schedule.timezone.from_local_datetime(&expected).earliest().is_some());
+            let local = schedule.ref_point_from_id(id).with_timezone(&schedule.timezone);
+            prop_assert_eq!(local.naive_local(), expected);
+        }
+    }
 }
@@ -0,0 +1,154 @@
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    QueryFilter,
+};
+use sha2::{Digest, Sha256};
+
+use crate::entities::{prelude::*, *};
+use crate::error::Result;
+
+pub const TOKEN_VERIFICACAO: &str = "verificacao";
+pub const TOKEN_RESET_SENHA: &str = "reset_senha";
+
+/// Hash guardado no banco, o token em si só existe no link enviado ao jogador.
+fn hash_token(token: &str) -> String {
+    let digest = Sha256::digest(token.as_bytes());
+    BASE64_URL_SAFE_NO_PAD.encode(digest)
+}
+
+/// Cria um token de uso único para o jogador e retorna o valor em texto claro.
+pub async fn create_token(
+    db: &DatabaseConnection,
+    jogador_id: i32,
+    tipo: &str,
+    validade: Duration,
+) -> Result<String> {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = BASE64_URL_SAFE_NO_PAD.encode(bytes);
+
+    token::ActiveModel {
+        jogador_id: ActiveValue::Set(jogador_id),
+        tipo: ActiveValue::Set(tipo.to_string()),
+        hash: ActiveValue::Set(hash_token(&token)),
+        expira_em: ActiveValue::Set(Utc::now() + validade),
+        usado_em: ActiveValue::Set(None),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(token)
+}
+
+/// Marca o token como usado e retorna o id do jogador dono dele.
+///
+/// Retorna `None` se o token não existe, é de outro tipo, já foi usado ou expirou.
+pub async fn consume_token<C: ConnectionTrait>(
+    db: &C,
+    tipo: &str,
+    token: &str,
+) -> Result<Option<i32>> {
+    let now = Utc::now();
+    let hash = hash_token(token);
+    // Checa e marca num UPDATE só, para duas requisições não usarem o mesmo token
+    let usado = Token::update_many()
+        .col_expr(token::Column::UsadoEm, Expr::value(now))
+        .filter(token::Column::Hash.eq(&hash))
+        .filter(token::Column::Tipo.eq(tipo))
+        .filter(token::Column::UsadoEm.is_null())
+        .filter(token::Column::ExpiraEm.gt(now))
+        .exec(db)
+        .await?;
+    if usado.rows_affected == 0 {
+        return Ok(None);
+    }
+
+    let found = Token::find()
+        .filter(token::Column::Hash.eq(hash))
+        .one(db)
+        .await?
+        .ok_or(anyhow::anyhow!("Token not found"))?;
+    Ok(Some(found.jogador_id))
+}
+
+/// Invalida os tokens do tipo ainda não usados do jogador, como depois de
+/// trocar a senha com um deles.
+pub async fn invalidate_tokens<C: ConnectionTrait>(
+    db: &C,
+    jogador_id: i32,
+    tipo: &str,
+) -> Result<()> {
+    Token::update_many()
+        .col_expr(token::Column::UsadoEm, Expr::value(Utc::now()))
+        .filter(token::Column::JogadorId.eq(jogador_id))
+        .filter(token::Column::Tipo.eq(tipo))
+        .filter(token::Column::UsadoEm.is_null())
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[tokio::test]
+    async fn token_so_vale_uma_vez() {
+        let db = fixtures::banco().await;
+        let ana = fixtures::cria_jogador(&db, "ana").await;
+        let token = create_token(&db, ana.id, TOKEN_RESET_SENHA, Duration::hours(1))
+            .await
+            .unwrap();
+
+        assert_eq!(consume_token(&db, TOKEN_RESET_SENHA, &token).await.unwrap(), Some(ana.id));
+        assert_eq!(consume_token(&db, TOKEN_RESET_SENHA, &token).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn token_expirado_nao_vale() {
+        let db = fixtures::banco().await;
+        let ana = fixtures::cria_jogador(&db, "ana").await;
+        let token = create_token(&db, ana.id, TOKEN_VERIFICACAO, Duration::seconds(-1))
+            .await
+            .unwrap();
+
+        assert_eq!(consume_token(&db, TOKEN_VERIFICACAO, &token).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn token_de_outro_tipo_nao_vale() {
+        let db = fixtures::banco().await;
+        let ana = fixtures::cria_jogador(&db, "ana").await;
+        let token = create_token(&db, ana.id, TOKEN_VERIFICACAO, Duration::hours(1))
+            .await
+            .unwrap();
+
+        assert_eq!(consume_token(&db, TOKEN_RESET_SENHA, &token).await.unwrap(), None);
+        assert_eq!(consume_token(&db, "outro", "inventado").await.unwrap(), None);
+        // A tentativa errada não gasta o token
+        assert_eq!(consume_token(&db, TOKEN_VERIFICACAO, &token).await.unwrap(), Some(ana.id));
+    }
+
+    #[tokio::test]
+    async fn invalida_so_os_tokens_do_jogador_e_do_tipo() {
+        let db = fixtures::banco().await;
+        let ana = fixtures::cria_jogador(&db, "ana").await;
+        let bia = fixtures::cria_jogador(&db, "bia").await;
+        let validade = Duration::hours(1);
+        let reset = create_token(&db, ana.id, TOKEN_RESET_SENHA, validade).await.unwrap();
+        let verificacao = create_token(&db, ana.id, TOKEN_VERIFICACAO, validade).await.unwrap();
+        let de_outro = create_token(&db, bia.id, TOKEN_RESET_SENHA, validade).await.unwrap();
+
+        invalidate_tokens(&db, ana.id, TOKEN_RESET_SENHA).await.unwrap();
+
+        assert_eq!(consume_token(&db, TOKEN_RESET_SENHA, &reset).await.unwrap(), None);
+        assert_eq!(consume_token(&db, TOKEN_VERIFICACAO, &verificacao).await.unwrap(), Some(ana.id));
+        assert_eq!(consume_token(&db, TOKEN_RESET_SENHA, &de_outro).await.unwrap(), Some(bia.id));
+    }
+}